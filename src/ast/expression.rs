@@ -1,57 +1,208 @@
 use core::fmt;
 
 use crate::ast::statement::Statement;
-use crate::token::Token;
+use crate::ast::types::Type;
+use crate::token::{Span, Token};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 // Expression（式）は値を生成する
 pub enum Expression {
     Identifier(String),
     IntegerLiteral(i32),
+    DoubleLiteral(f64),
     BooleanLiteral(bool),
+    StringLiteral(String), // エスケープ解除後の内容
+    ArrayLiteral(Vec<Expression>),
+    HashLiteral(Vec<(Expression, Expression)>), // (キー, 値) を書いた順に持つ
     FunctionLiteral {
-        parameters: Vec<Box<Expression>>, // Identifier
-        body: Box<Statement>,             // BlockStatement
+        parameters: Vec<Parameter>,
+        return_type: Option<Type>,
+        body: Box<Statement>, // BlockStatement
     },
     PrefixExpression {
-        token: Token,
+        token: Token<'static>,
         operator: String,
         right: Box<Expression>,
     },
     InfixExpression {
-        token: Token,
+        token: Token<'static>,
         left: Box<Expression>,
         operator: String,
         right: Box<Expression>,
     },
+    // x = v や a[i] += v。target は Identifier, IndexExpression, MemberAccessExpression のいずれか
+    AssignExpression {
+        token: Token<'static>,
+        target: Box<Expression>,
+        operator: String, // = += -= *= /=
+        value: Box<Expression>,
+    },
+    // && と ||。右辺は左辺で結果が決まらないときだけ評価する
+    LogicalExpression {
+        token: Token<'static>,
+        left: Box<Expression>,
+        operator: String,
+        right: Box<Expression>,
+    },
+    // start..end（inclusive なら start..=end）
+    RangeExpression {
+        token: Token<'static>,
+        start: Box<Expression>,
+        end: Box<Expression>,
+        inclusive: bool,
+    },
     IfExpression {
         condition: Box<Expression>,
         consequence: Box<Statement>,
         alternative: Option<Box<Statement>>,
     },
     CallExpression {
-        function: Box<Expression>, // Identifier or FunctionLiteral
-        arguments: Vec<Box<Expression>>,
+        function: Box<Expression>, // Identifier, FunctionLiteral or MemberAccessExpression
+        arguments: Vec<Expression>,
+    },
+    MemberAccessExpression {
+        object: Box<Expression>,
+        member: String,
+    },
+    IndexExpression {
+        left: Box<Expression>,
+        index: Box<Expression>,
+    },
+    // a[start:end]。省略した側は先頭・末尾
+    SliceExpression {
+        left: Box<Expression>,
+        start: Option<Box<Expression>>,
+        end: Option<Box<Expression>>,
     },
 }
 
+// 関数の仮引数。型は int a と a: int のどちらでも書ける
+#[derive(Debug, Clone)]
+pub struct Parameter {
+    pub name: String,
+    pub param_type: Option<Type>,
+}
+
+impl fmt::Display for Parameter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.param_type {
+            Some(param_type) => write!(f, "{}: {}", self.name, param_type),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+// fn (a: int, b) -> int の部分（本体は含まない）。name があれば fn add(a: int, b) -> int
+pub(crate) fn write_signature(
+    f: &mut fmt::Formatter<'_>,
+    name: Option<&str>,
+    parameters: &[Parameter],
+    return_type: &Option<Type>,
+) -> fmt::Result {
+    match name {
+        Some(name) => write!(f, "fn {}", name)?,
+        None => write!(f, "fn ")?,
+    }
+    write!(
+        f,
+        "({})",
+        parameters
+            .iter()
+            .map(|p| format!("{}", p))
+            .collect::<Vec<String>>()
+            .join(", ")
+    )?;
+    if let Some(return_type) = return_type {
+        write!(f, " -> {}", return_type)?;
+    }
+    Ok(())
+}
+
+impl Expression {
+    // 演算子のトークンの位置。トークンを持たない式は None
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Expression::PrefixExpression { token, .. }
+            | Expression::InfixExpression { token, .. }
+            | Expression::AssignExpression { token, .. }
+            | Expression::LogicalExpression { token, .. }
+            | Expression::RangeExpression { token, .. } => Some(token.span),
+            _ => None,
+        }
+    }
+
+    // リテラルならその値の型（-1 や -1.5 も含む）。それ以外は実行するまで分からないので None
+    pub fn literal_type(&self) -> Option<Type> {
+        match self {
+            Expression::IntegerLiteral(_) => Some(Type::Int),
+            Expression::DoubleLiteral(_) => Some(Type::Double),
+            Expression::BooleanLiteral(_) => Some(Type::Boolean),
+            Expression::StringLiteral(_) => Some(Type::String),
+            Expression::ArrayLiteral(_) => Some(Type::Array),
+            Expression::HashLiteral(_) => Some(Type::Hash),
+            Expression::FunctionLiteral { .. } => Some(Type::Function),
+            Expression::PrefixExpression {
+                operator, right, ..
+            } if operator == "-" => right
+                .literal_type()
+                .filter(|t| matches!(t, Type::Int | Type::Double)),
+            _ => None,
+        }
+    }
+}
+
+// ソースに書ける形にエスケープする（読み直すと同じ内容になる）
+pub(crate) fn escape_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            '\0' => escaped.push_str("\\0"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
 impl fmt::Display for Expression {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Expression::Identifier(s) => write!(f, "{}", s)?,
             Expression::IntegerLiteral(i) => write!(f, "{}", i)?,
+            // {:?} は整数値でも 1.0 のように小数点を付ける
+            Expression::DoubleLiteral(d) => write!(f, "{:?}", d)?,
             Expression::BooleanLiteral(b) => write!(f, "{}", b)?,
-            Expression::FunctionLiteral { parameters, body } => {
-                write!(
-                    f,
-                    "fn ({}) {}",
-                    parameters
-                        .iter()
-                        .map(|p| format!("{}", p))
-                        .collect::<Vec<String>>()
-                        .join(", "),
-                    body
-                )?;
+            Expression::StringLiteral(s) => write!(f, "\"{}\"", escape_string(s))?,
+            Expression::ArrayLiteral(elements) => write!(
+                f,
+                "[{}]",
+                elements
+                    .iter()
+                    .map(|e| format!("{}", e))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            )?,
+            Expression::HashLiteral(pairs) => write!(
+                f,
+                "{{{}}}",
+                pairs
+                    .iter()
+                    .map(|(k, v)| format!("{}: {}", k, v))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            )?,
+            Expression::FunctionLiteral {
+                parameters,
+                return_type,
+                body,
+            } => {
+                write_signature(f, None, parameters, return_type)?;
+                write!(f, " {}", body)?;
             }
             Expression::PrefixExpression {
                 operator, right, ..
@@ -61,7 +212,28 @@ impl fmt::Display for Expression {
                 operator,
                 right,
                 ..
+            }
+            | Expression::LogicalExpression {
+                left,
+                operator,
+                right,
+                ..
             } => write!(f, "({} {} {})", left, operator, right)?,
+            Expression::AssignExpression {
+                target,
+                operator,
+                value,
+                ..
+            } => write!(f, "({} {} {})", target, operator, value)?,
+            Expression::RangeExpression {
+                start,
+                end,
+                inclusive,
+                ..
+            } => {
+                let operator = if *inclusive { "..=" } else { ".." };
+                write!(f, "({}{}{})", start, operator, end)?
+            }
             Expression::IfExpression {
                 condition,
                 consequence,
@@ -90,6 +262,21 @@ impl fmt::Display for Expression {
                         .join(", "),
                 )?;
             }
+            Expression::MemberAccessExpression { object, member } => {
+                write!(f, "{}.{}", object, member)?
+            }
+            Expression::IndexExpression { left, index } => write!(f, "({}[{}])", left, index)?,
+            Expression::SliceExpression { left, start, end } => {
+                write!(f, "({}[", left)?;
+                if let Some(start) = start {
+                    write!(f, "{}", start)?;
+                }
+                write!(f, ":")?;
+                if let Some(end) = end {
+                    write!(f, "{}", end)?;
+                }
+                write!(f, "])")?
+            }
         };
         Ok(())
     }
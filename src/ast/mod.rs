@@ -1,3 +1,4 @@
 pub mod expression;
 pub mod program;
 pub mod statement;
+pub mod types;
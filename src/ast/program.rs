@@ -1,7 +1,10 @@
+use core::fmt;
+
 use super::statement::Statement;
 
+#[derive(Debug, Default)]
 pub struct Program {
-    statements: Vec<Statement>,
+    pub statements: Vec<Statement>,
 }
 
 impl Program {
@@ -11,3 +14,12 @@ impl Program {
         }
     }
 }
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for stmt in &self.statements {
+            writeln!(f, "{}", stmt)?;
+        }
+        Ok(())
+    }
+}
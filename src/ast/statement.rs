@@ -1,46 +1,162 @@
 use core::fmt;
 
-use super::expression::Expression;
-use crate::token::Token;
+use super::expression::{write_signature, Expression, Parameter};
+use super::types::Type;
+use crate::token::{Span, Token};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Statement {
     LetStatement {
-        token: Token,
+        token: Token<'static>,
+        doc: Option<String>, // 直前のドキュメントコメント
         identfier: Expression,
+        var_type: Option<Type>, // int x = ... や let x: int = ... の型
         value: Expression,
     },
     ReturnStatement {
-        token: Token,
-        return_value: Expression,
+        token: Token<'static>,
+        return_value: Option<Expression>, // return; なら None
     },
     ExpressionStatement {
-        token: Token,
+        token: Token<'static>,
         expression: Expression,
     },
     BlockStatement {
-        token: Token,
+        token: Token<'static>,
         statements: Vec<Statement>,
     },
+    // fn add(a, b) { ... }。同じスコープの先頭に巻き上げられる
+    FunctionDeclarationStatement {
+        token: Token<'static>,
+        doc: Option<String>,
+        name: String,
+        parameters: Vec<Parameter>,
+        return_type: Option<Type>,
+        body: Box<Statement>, // BlockStatement
+    },
+    WhileStatement {
+        token: Token<'static>,
+        label: Option<String>, // 'outer: while ... の outer
+        condition: Expression,
+        body: Box<Statement>, // BlockStatement
+    },
+    // for x in iterable { } / for k, v in iterable { }
+    ForStatement {
+        token: Token<'static>,
+        label: Option<String>,
+        variables: Vec<String>, // 1 つか 2 つ
+        iterable: Expression,
+        body: Box<Statement>, // BlockStatement
+    },
+    // label がなければ一番内側のループが対象
+    BreakStatement {
+        token: Token<'static>,
+        label: Option<String>,
+    },
+    ContinueStatement {
+        token: Token<'static>,
+        label: Option<String>,
+    },
+    // 構文エラーで解析できなかった文（token はその文の先頭）
+    ErrorStatement {
+        token: Token<'static>,
+    },
+}
+
+impl Statement {
+    // 文の先頭のトークンの位置
+    pub fn span(&self) -> Span {
+        match self {
+            Statement::LetStatement { token, .. }
+            | Statement::ReturnStatement { token, .. }
+            | Statement::ExpressionStatement { token, .. }
+            | Statement::BlockStatement { token, .. }
+            | Statement::FunctionDeclarationStatement { token, .. }
+            | Statement::WhileStatement { token, .. }
+            | Statement::ForStatement { token, .. }
+            | Statement::BreakStatement { token, .. }
+            | Statement::ContinueStatement { token, .. }
+            | Statement::ErrorStatement { token } => token.span,
+        }
+    }
 }
 
 impl fmt::Display for Statement {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Statement::LetStatement {
-                identfier, value, ..
-            } => write!(f, "let {} = {};", identfier, value)?,
-            Statement::ReturnStatement { return_value, .. } => {
-                write!(f, "return {};", return_value)?
+                doc,
+                identfier,
+                var_type,
+                value,
+                ..
+            } => {
+                for line in doc.iter().flat_map(|doc| doc.lines()) {
+                    writeln!(f, "/// {}", line)?;
+                }
+                match var_type {
+                    Some(var_type) => write!(f, "let {}: {} = {};", identfier, var_type, value)?,
+                    None => write!(f, "let {} = {};", identfier, value)?,
+                }
             }
+            Statement::ReturnStatement { return_value, .. } => match return_value {
+                Some(return_value) => write!(f, "return {};", return_value)?,
+                None => write!(f, "return;")?,
+            },
             Statement::ExpressionStatement { expression, .. } => write!(f, "{}", expression)?,
             Statement::BlockStatement { statements, .. } => {
-                write!(f, "{{\n")?;
+                writeln!(f, "{{")?;
                 for stmt in statements {
                     write!(f, "{}", stmt)?;
                 }
                 write!(f, "\n}}")?;
             }
+            Statement::FunctionDeclarationStatement {
+                doc,
+                name,
+                parameters,
+                return_type,
+                body,
+                ..
+            } => {
+                for line in doc.iter().flat_map(|doc| doc.lines()) {
+                    writeln!(f, "/// {}", line)?;
+                }
+                write_signature(f, Some(name), parameters, return_type)?;
+                write!(f, " {}", body)?
+            }
+            Statement::WhileStatement {
+                label,
+                condition,
+                body,
+                ..
+            } => {
+                if let Some(label) = label {
+                    write!(f, "'{}: ", label)?;
+                }
+                write!(f, "while ({}) {}", condition, body)?
+            }
+            Statement::ForStatement {
+                label,
+                variables,
+                iterable,
+                body,
+                ..
+            } => {
+                if let Some(label) = label {
+                    write!(f, "'{}: ", label)?;
+                }
+                write!(f, "for {} in {} {}", variables.join(", "), iterable, body)?
+            }
+            Statement::BreakStatement { label, .. } => match label {
+                Some(label) => write!(f, "break '{};", label)?,
+                None => write!(f, "break;")?,
+            },
+            Statement::ContinueStatement { label, .. } => match label {
+                Some(label) => write!(f, "continue '{};", label)?,
+                None => write!(f, "continue;")?,
+            },
+            Statement::ErrorStatement { .. } => write!(f, "<error>")?,
         };
         Ok(())
     }
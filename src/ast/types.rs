@@ -0,0 +1,55 @@
+use core::fmt;
+
+use crate::token::TokenKind;
+
+// 値の型。型注釈（int x = 1; や let x: int = 1; の int）に書けるのは int, double, boolean, void
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Type {
+    Int,
+    Double,
+    Boolean,
+    String,
+    Array,
+    Hash,
+    Range,
+    Function,
+    Void,
+}
+
+impl Type {
+    // 型名のキーワードに対応する型
+    pub fn from_token_kind(kind: TokenKind) -> Option<Type> {
+        match kind {
+            TokenKind::Int => Some(Type::Int),
+            TokenKind::Double => Some(Type::Double),
+            TokenKind::Boolean => Some(Type::Boolean),
+            TokenKind::Void => Some(Type::Void),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Type::Int => "int",
+            Type::Double => "double",
+            Type::Boolean => "boolean",
+            Type::String => "string",
+            Type::Array => "array",
+            Type::Hash => "hash",
+            Type::Range => "range",
+            Type::Function => "function",
+            Type::Void => "void",
+        }
+    }
+
+    // found 型の値をこの型の変数に入れられるかどうか（int は double に広げられる）
+    pub fn accepts(&self, found: Type) -> bool {
+        *self == found || (*self == Type::Double && found == Type::Int)
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
@@ -0,0 +1,70 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::ast::types::Type;
+use crate::value::Value;
+
+pub type Env = Rc<RefCell<Environment>>;
+
+// 変数のスコープ。見つからなければ外側のスコープを探す
+#[derive(Debug, Default)]
+pub struct Environment {
+    store: HashMap<String, Value>,
+    types: HashMap<String, Type>, // 型注釈付きで宣言された変数の型
+    outer: Option<Env>,
+}
+
+impl Environment {
+    pub fn new() -> Env {
+        Rc::new(RefCell::new(Environment::default()))
+    }
+
+    // outer を外側に持つ新しいスコープ
+    pub fn enclosed(outer: &Env) -> Env {
+        Rc::new(RefCell::new(Environment {
+            store: HashMap::new(),
+            types: HashMap::new(),
+            outer: Some(Rc::clone(outer)),
+        }))
+    }
+
+    pub fn get(&self, name: &str) -> Option<Value> {
+        match self.store.get(name) {
+            Some(value) => Some(value.clone()),
+            None => self.outer.as_ref()?.borrow().get(name),
+        }
+    }
+
+    // このスコープに変数を定義する（同名があれば上書き）
+    pub fn define(&mut self, name: &str, value: Value) {
+        self.types.remove(name);
+        self.store.insert(name.to_string(), value);
+    }
+
+    // 型注釈付きで定義する。以降の代入も var_type の値に限られる
+    pub fn define_typed(&mut self, name: &str, value: Value, var_type: Type) {
+        self.store.insert(name.to_string(), value);
+        self.types.insert(name.to_string(), var_type);
+    }
+
+    // 変数の宣言された型。型注釈のない変数や見つからない変数は None
+    pub fn declared_type(&self, name: &str) -> Option<Type> {
+        if self.store.contains_key(name) {
+            return self.types.get(name).copied();
+        }
+        self.outer.as_ref()?.borrow().declared_type(name)
+    }
+
+    // 既存の変数に代入する。見つからなければ false
+    pub fn assign(&mut self, name: &str, value: Value) -> bool {
+        if let Some(slot) = self.store.get_mut(name) {
+            *slot = value;
+            return true;
+        }
+        match &self.outer {
+            Some(outer) => outer.borrow_mut().assign(name, value),
+            None => false,
+        }
+    }
+}
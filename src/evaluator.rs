@@ -0,0 +1,1371 @@
+use core::fmt;
+use std::cell::RefCell;
+use std::error;
+use std::ops::Range;
+use std::rc::Rc;
+
+use crate::ast::{expression::Expression, program::Program, statement::Statement, types::Type};
+use crate::environment::{Env, Environment};
+use crate::stack::ensure_sufficient_stack;
+use crate::token::Span;
+use crate::value::{Function, Hash, HashKey, Value};
+
+// 関数呼び出しの深さの上限（無限再帰を止めるため）
+const MAX_CALL_DEPTH: usize = 10_000;
+
+// 評価の途中で抜けるときの理由
+enum Unwind {
+    Return(Value),
+    Break(Option<String>), // ラベル
+    Continue(Option<String>),
+    Error(EvalError),
+}
+
+impl Unwind {
+    // 関数やプログラムの外まで出てしまったものはエラーにする
+    // （パーサがループの外の break / continue を弾くので通常は起きない）
+    fn into_result(self) -> Result<Value, EvalError> {
+        match self {
+            Unwind::Return(value) => Ok(value),
+            Unwind::Break(_) => EvalError::throw("break outside of a loop".to_string()),
+            Unwind::Continue(_) => EvalError::throw("continue outside of a loop".to_string()),
+            Unwind::Error(err) => Err(err),
+        }
+    }
+
+    // label のループを対象にした break / continue かどうか（ラベルなしは一番内側が対象）
+    fn targets(target: &Option<String>, label: &Option<String>) -> bool {
+        target.is_none() || target == label
+    }
+}
+
+impl Unwind {
+    // エラーにまだ位置がなければ span を付ける（いちばん内側の位置が残る）
+    fn at(self, span: Span) -> Self {
+        match self {
+            Unwind::Error(err) => Unwind::Error(err.at(span)),
+            other => other,
+        }
+    }
+}
+
+impl From<EvalError> for Unwind {
+    fn from(err: EvalError) -> Self {
+        Unwind::Error(err)
+    }
+}
+
+type EvalResult = Result<Value, Unwind>;
+
+pub struct Evaluator {
+    env: Env, // トップレベルの環境。eval_program をまたいで保持する
+    depth: usize,
+}
+
+impl Default for Evaluator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Evaluator {
+    pub fn new() -> Self {
+        Evaluator {
+            env: Environment::new(),
+            depth: 0,
+        }
+    }
+
+    // 最後に評価した文の値を返す
+    pub fn eval_program(&mut self, program: &Program) -> Result<Value, EvalError> {
+        let env = Rc::clone(&self.env);
+        hoist_functions(&program.statements, &env);
+        let mut result = Value::Void;
+        for stmt in &program.statements {
+            match self.eval_statement(stmt, &env) {
+                Ok(value) => result = value,
+                Err(unwind) => return unwind.into_result(),
+            }
+        }
+        Ok(result)
+    }
+
+    // 再帰が深くなるので、各 match の腕は別の関数に分けてスタックフレームを小さく保つ
+    fn eval_statement(&mut self, stmt: &Statement, env: &Env) -> EvalResult {
+        self.eval_statement_inner(stmt, env)
+            .map_err(|unwind| unwind.at(stmt.span()))
+    }
+
+    fn eval_statement_inner(&mut self, stmt: &Statement, env: &Env) -> EvalResult {
+        match stmt {
+            Statement::LetStatement {
+                identfier,
+                var_type,
+                value,
+                ..
+            } => {
+                let value = self.eval_expression(value, env)?;
+                let name = identfier.to_string();
+                match var_type {
+                    Some(var_type) => {
+                        let value = check_type(*var_type, value)?;
+                        env.borrow_mut().define_typed(&name, value, *var_type)
+                    }
+                    None => env.borrow_mut().define(&name, value),
+                }
+                Ok(Value::Void)
+            }
+            Statement::ReturnStatement { return_value, .. } => {
+                let value = match return_value {
+                    Some(return_value) => self.eval_expression(return_value, env)?,
+                    None => Value::Void,
+                };
+                Err(Unwind::Return(value))
+            }
+            Statement::ExpressionStatement { expression, .. } => {
+                self.eval_expression(expression, env)
+            }
+            Statement::BlockStatement { statements, .. } => self.eval_block(statements, env),
+            // 関数はスコープに入ったときに定義済み（hoist_functions）
+            Statement::FunctionDeclarationStatement { .. } => Ok(Value::Void),
+            Statement::WhileStatement {
+                label,
+                condition,
+                body,
+                ..
+            } => self.eval_while_statement(label, condition, body, env),
+            Statement::ForStatement {
+                label,
+                variables,
+                iterable,
+                body,
+                ..
+            } => self.eval_for_statement(label, variables, iterable, body, env),
+            Statement::BreakStatement { label, .. } => Err(Unwind::Break(label.clone())),
+            Statement::ContinueStatement { label, .. } => Err(Unwind::Continue(label.clone())),
+            Statement::ErrorStatement { token } => Err(EvalError::new(format!(
+                "cannot evaluate invalid statement at {}",
+                token.span
+            ))
+            .into()),
+        }
+    }
+
+    fn eval_block(&mut self, statements: &[Statement], env: &Env) -> EvalResult {
+        let env = Environment::enclosed(env);
+        hoist_functions(statements, &env);
+        let mut result = Value::Void;
+        for stmt in statements {
+            result = self.eval_statement(stmt, &env)?;
+        }
+        Ok(result)
+    }
+
+    fn eval_while_statement(
+        &mut self,
+        label: &Option<String>,
+        condition: &Expression,
+        body: &Statement,
+        env: &Env,
+    ) -> EvalResult {
+        while expect_bool("while", self.eval_expression(condition, env)?)? {
+            if !self.eval_loop_body(label, body, env)? {
+                break;
+            }
+        }
+        Ok(Value::Void)
+    }
+
+    fn eval_for_statement(
+        &mut self,
+        label: &Option<String>,
+        variables: &[String],
+        iterable: &Expression,
+        body: &Statement,
+        env: &Env,
+    ) -> EvalResult {
+        let items = iteration_items(self.eval_expression(iterable, env)?, variables.len())?;
+        for values in items {
+            // 1 回ごとに新しいスコープに変数を束縛する
+            let env = Environment::enclosed(env);
+            for (name, value) in variables.iter().zip(values) {
+                env.borrow_mut().define(name, value);
+            }
+            if !self.eval_loop_body(label, body, &env)? {
+                break;
+            }
+        }
+        Ok(Value::Void)
+    }
+
+    // ループの本体を 1 回評価する。ループを続けるなら true
+    fn eval_loop_body(
+        &mut self,
+        label: &Option<String>,
+        body: &Statement,
+        env: &Env,
+    ) -> Result<bool, Unwind> {
+        match self.eval_statement(body, env) {
+            Ok(_) => Ok(true),
+            Err(Unwind::Break(target)) if Unwind::targets(&target, label) => Ok(false),
+            Err(Unwind::Continue(target)) if Unwind::targets(&target, label) => Ok(true),
+            Err(unwind) => Err(unwind),
+        }
+    }
+
+    fn eval_expression(&mut self, expr: &Expression, env: &Env) -> EvalResult {
+        let result = ensure_sufficient_stack(|| self.eval_expression_inner(expr, env));
+        match expr.span() {
+            Some(span) => result.map_err(|unwind| unwind.at(span)),
+            None => result,
+        }
+    }
+
+    fn eval_expression_inner(&mut self, expr: &Expression, env: &Env) -> EvalResult {
+        match expr {
+            Expression::Identifier(name) => Ok(eval_identifier(name, env)?),
+            Expression::IntegerLiteral(i) => Ok(Value::Int(*i)),
+            Expression::DoubleLiteral(d) => Ok(Value::Double(*d)),
+            Expression::BooleanLiteral(b) => Ok(Value::Bool(*b)),
+            Expression::StringLiteral(s) => Ok(Value::Str(s.clone())),
+            Expression::ArrayLiteral(elements) => {
+                let elements = self.eval_expressions(elements, env)?;
+                Ok(Value::Array(Rc::new(RefCell::new(elements.into()))))
+            }
+            Expression::HashLiteral(pairs) => self.eval_hash_literal(pairs, env),
+            Expression::FunctionLiteral {
+                parameters,
+                return_type,
+                body,
+            } => Ok(Value::Function(Rc::new(Function {
+                name: None,
+                parameters: parameters.clone(),
+                return_type: *return_type,
+                body: body.as_ref().clone(),
+                env: Rc::clone(env),
+            }))),
+            Expression::PrefixExpression {
+                operator, right, ..
+            } => {
+                let right = self.eval_expression(right, env)?;
+                Ok(eval_prefix(operator, right)?)
+            }
+            Expression::InfixExpression {
+                left,
+                operator,
+                right,
+                ..
+            } => self.eval_infix_expression(left, operator, right, env),
+            Expression::LogicalExpression {
+                left,
+                operator,
+                right,
+                ..
+            } => self.eval_logical_expression(left, operator, right, env),
+            Expression::AssignExpression {
+                target,
+                operator,
+                value,
+                ..
+            } => self.eval_assign_expression(target, operator, value, env),
+            Expression::RangeExpression {
+                start,
+                end,
+                inclusive,
+                ..
+            } => {
+                let start = self.eval_expression(start, env)?;
+                let end = self.eval_expression(end, env)?;
+                match (start, end) {
+                    (Value::Int(start), Value::Int(end)) => Ok(Value::Range {
+                        start,
+                        end,
+                        inclusive: *inclusive,
+                    }),
+                    (start, end) => Err(EvalError::new(format!(
+                        "range bounds must be int, got {}..{}",
+                        start.value_type(),
+                        end.value_type()
+                    ))
+                    .into()),
+                }
+            }
+            Expression::IfExpression {
+                condition,
+                consequence,
+                alternative,
+            } => self.eval_if_expression(condition, consequence, alternative.as_deref(), env),
+            Expression::CallExpression {
+                function,
+                arguments,
+            } => self.eval_call_expression(function, arguments, env),
+            Expression::IndexExpression { left, index } => {
+                let left = self.eval_expression(left, env)?;
+                let index = self.eval_expression(index, env)?;
+                Ok(eval_index(left, index)?)
+            }
+            Expression::SliceExpression { left, start, end } => {
+                self.eval_slice_expression(left, start.as_deref(), end.as_deref(), env)
+            }
+            Expression::MemberAccessExpression { object, member } => Err(EvalError::new(format!(
+                "member access is not supported: {}.{}",
+                object, member
+            ))
+            .into()),
+        }
+    }
+
+    fn eval_infix_expression(
+        &mut self,
+        left: &Expression,
+        operator: &str,
+        right: &Expression,
+        env: &Env,
+    ) -> EvalResult {
+        let left = self.eval_expression(left, env)?;
+        let right = self.eval_expression(right, env)?;
+        Ok(eval_infix(operator, left, right)?)
+    }
+
+    // 左辺で結果が決まれば右辺は評価しない
+    fn eval_logical_expression(
+        &mut self,
+        left: &Expression,
+        operator: &str,
+        right: &Expression,
+        env: &Env,
+    ) -> EvalResult {
+        let left = expect_bool(operator, self.eval_expression(left, env)?)?;
+        match (operator, left) {
+            ("&&", false) => Ok(Value::Bool(false)),
+            ("||", true) => Ok(Value::Bool(true)),
+            _ => {
+                let right = expect_bool(operator, self.eval_expression(right, env)?)?;
+                Ok(Value::Bool(right))
+            }
+        }
+    }
+
+    fn eval_if_expression(
+        &mut self,
+        condition: &Expression,
+        consequence: &Statement,
+        alternative: Option<&Statement>,
+        env: &Env,
+    ) -> EvalResult {
+        let condition = expect_bool("if", self.eval_expression(condition, env)?)?;
+        match (condition, alternative) {
+            (true, _) => self.eval_statement(consequence, env),
+            (false, Some(alt)) => self.eval_statement(alt, env),
+            (false, None) => Ok(Value::Void),
+        }
+    }
+
+    fn eval_call_expression(
+        &mut self,
+        function: &Expression,
+        arguments: &[Expression],
+        env: &Env,
+    ) -> EvalResult {
+        let function = self.eval_expression(function, env)?;
+        let args = self.eval_expressions(arguments, env)?;
+        Ok(self.apply_function(function, args)?)
+    }
+
+    fn eval_expressions(
+        &mut self,
+        expressions: &[Expression],
+        env: &Env,
+    ) -> Result<Vec<Value>, Unwind> {
+        let mut values = Vec::with_capacity(expressions.len());
+        for expr in expressions {
+            values.push(self.eval_expression(expr, env)?);
+        }
+        Ok(values)
+    }
+
+    fn eval_hash_literal(&mut self, pairs: &[(Expression, Expression)], env: &Env) -> EvalResult {
+        let mut hash = Hash::new();
+        for (key, value) in pairs {
+            let key = hash_key(self.eval_expression(key, env)?)?;
+            let value = self.eval_expression(value, env)?;
+            hash.insert(key, value);
+        }
+        Ok(Value::Hash(Rc::new(RefCell::new(hash))))
+    }
+
+    fn eval_slice_expression(
+        &mut self,
+        left: &Expression,
+        start: Option<&Expression>,
+        end: Option<&Expression>,
+        env: &Env,
+    ) -> EvalResult {
+        let left = self.eval_expression(left, env)?;
+        let mut bounds = [None, None];
+        for (bound, expr) in bounds.iter_mut().zip([start, end]) {
+            if let Some(expr) = expr {
+                match self.eval_expression(expr, env)? {
+                    Value::Int(i) => *bound = Some(i),
+                    other => {
+                        return Err(EvalError::new(format!(
+                            "slice index must be int, got {}",
+                            other.value_type()
+                        ))
+                        .into())
+                    }
+                }
+            }
+        }
+        let [start, end] = bounds;
+
+        match left {
+            Value::Array(elements) => {
+                let elements = elements.borrow();
+                let range = slice_range(start, end, elements.len())?;
+                let sliced = elements[range].to_vec();
+                Ok(Value::Array(Rc::new(RefCell::new(sliced.into()))))
+            }
+            Value::Str(s) => {
+                let chars = s.chars().collect::<Vec<char>>();
+                let range = slice_range(start, end, chars.len())?;
+                Ok(Value::Str(chars[range].iter().collect()))
+            }
+            other => Err(EvalError::new(format!(
+                "slice operator not supported: {}",
+                other.value_type()
+            ))
+            .into()),
+        }
+    }
+
+    // 代入した値を返す。x += y は x の今の値と y から新しい値を作る
+    fn eval_assign_expression(
+        &mut self,
+        target: &Expression,
+        operator: &str,
+        value: &Expression,
+        env: &Env,
+    ) -> EvalResult {
+        match target {
+            Expression::Identifier(name) => {
+                let value = self.eval_expression(value, env)?;
+                let value = match operator.strip_suffix('=') {
+                    Some(op) if !op.is_empty() => {
+                        eval_infix(op, eval_identifier(name, env)?, value)?
+                    }
+                    _ => value,
+                };
+                // 型注釈付きの変数には、宣言した型の値しか入れられない
+                let declared_type = env.borrow().declared_type(name);
+                let value = match declared_type {
+                    Some(var_type) => check_type(var_type, value)?,
+                    None => value,
+                };
+                if !env.borrow_mut().assign(name, value.clone()) {
+                    return Err(EvalError::new(format!("undefined variable: {}", name)).into());
+                }
+                Ok(value)
+            }
+            Expression::IndexExpression { left, index } => {
+                let container = self.eval_expression(left, env)?;
+                let index = self.eval_expression(index, env)?;
+                let value = self.eval_expression(value, env)?;
+                let value = match operator.strip_suffix('=') {
+                    Some(op) if !op.is_empty() => {
+                        let current = eval_index(container.clone(), index.clone())?;
+                        eval_infix(op, current, value)?
+                    }
+                    _ => value,
+                };
+                assign_index(container, index, value.clone())?;
+                Ok(value)
+            }
+            Expression::MemberAccessExpression { object, member } => Err(EvalError::new(format!(
+                "member access is not supported: {}.{}",
+                object, member
+            ))
+            .into()),
+            // パーサが弾くので通常は来ない
+            other => Err(EvalError::new(format!("invalid assignment target: {}", other)).into()),
+        }
+    }
+
+    fn apply_function(&mut self, function: Value, args: Vec<Value>) -> Result<Value, EvalError> {
+        let Value::Function(func) = function else {
+            return EvalError::throw(format!("not a function: {}", function.value_type()));
+        };
+        if func.parameters.len() != args.len() {
+            return EvalError::throw(format!(
+                "wrong number of arguments: expected {}, got {}",
+                func.parameters.len(),
+                args.len()
+            ));
+        }
+        if self.depth >= MAX_CALL_DEPTH {
+            return EvalError::throw("maximum call depth exceeded".to_string());
+        }
+
+        let env = bind_arguments(&func, args)?;
+
+        self.depth += 1;
+        let result = self.eval_statement(&func.body, &env);
+        self.depth -= 1;
+        check_return(&func, result)
+    }
+}
+
+// スコープ内の関数宣言を、文を評価する前にまとめて定義する
+// どれも同じ env を捕捉するので、宣言の順によらず互いに呼び出せる
+fn hoist_functions(statements: &[Statement], env: &Env) {
+    for stmt in statements {
+        if let Statement::FunctionDeclarationStatement {
+            name,
+            parameters,
+            return_type,
+            body,
+            ..
+        } = stmt
+        {
+            let function = Function {
+                name: Some(name.clone()),
+                parameters: parameters.clone(),
+                return_type: *return_type,
+                body: body.as_ref().clone(),
+                env: Rc::clone(env),
+            };
+            env.borrow_mut()
+                .define(name, Value::Function(Rc::new(function)));
+        }
+    }
+}
+
+// 引数を仮引数の名前で束縛した、関数本体用の環境を作る
+fn bind_arguments(func: &Function, args: Vec<Value>) -> Result<Env, EvalError> {
+    let env = Environment::enclosed(&func.env);
+    for (param, arg) in func.parameters.iter().zip(args) {
+        match param.param_type {
+            Some(param_type) => {
+                let arg = check_type(param_type, arg)
+                    .map_err(|err| err.context(&format!("argument {}", param.name)))?;
+                env.borrow_mut().define_typed(&param.name, arg, param_type);
+            }
+            None => env.borrow_mut().define(&param.name, arg),
+        }
+    }
+    Ok(env)
+}
+
+// 関数本体の評価結果から戻り値を取り出し、戻り値の型を確かめる
+fn check_return(func: &Function, result: EvalResult) -> Result<Value, EvalError> {
+    let value = match (result, func.return_type) {
+        // void の関数では最後の文の値は捨てる（return で返した値だけを確かめる）
+        (Ok(_), Some(Type::Void)) => Value::Void,
+        (Ok(value), _) => value,
+        (Err(unwind), _) => unwind.into_result()?,
+    };
+    match func.return_type {
+        Some(return_type) => {
+            check_type(return_type, value).map_err(|err| err.context("return value"))
+        }
+        None => Ok(value),
+    }
+}
+
+fn eval_identifier(name: &str, env: &Env) -> Result<Value, EvalError> {
+    match env.borrow().get(name) {
+        Some(value) => Ok(value),
+        None => EvalError::throw(format!("undefined variable: {}", name)),
+    }
+}
+
+// 型注釈と値が合うか確かめる。double には int を double にして入れる
+fn check_type(expected: Type, value: Value) -> Result<Value, EvalError> {
+    match value {
+        Value::Int(i) if expected == Type::Double => Ok(Value::Double(f64::from(i))),
+        value if expected.accepts(value.value_type()) => Ok(value),
+        value => EvalError::throw(format!(
+            "mismatched types: expected {}, found {}",
+            expected,
+            value.value_type()
+        )),
+    }
+}
+
+fn expect_bool(context: &str, value: Value) -> Result<bool, EvalError> {
+    match value {
+        Value::Bool(b) => Ok(b),
+        other => EvalError::throw(format!(
+            "expected boolean operand for {}, got {}",
+            context,
+            other.value_type()
+        )),
+    }
+}
+
+fn eval_prefix(operator: &str, right: Value) -> Result<Value, EvalError> {
+    match (operator, right) {
+        ("!", Value::Bool(b)) => Ok(Value::Bool(!b)),
+        ("-", Value::Int(i)) => match i.checked_neg() {
+            Some(i) => Ok(Value::Int(i)),
+            None => EvalError::throw(format!("integer overflow: -({})", i)),
+        },
+        ("-", Value::Double(d)) => Ok(Value::Double(-d)),
+        (op, right) => EvalError::throw(format!("unknown operator: {}{}", op, right.value_type())),
+    }
+}
+
+fn eval_infix(operator: &str, left: Value, right: Value) -> Result<Value, EvalError> {
+    match (left, right) {
+        (Value::Int(l), Value::Int(r)) => eval_integer_infix(operator, l, r),
+        // 片方が double ならもう片方も double にして計算する
+        (Value::Double(l), Value::Double(r)) => eval_double_infix(operator, l, r),
+        (Value::Int(l), Value::Double(r)) => eval_double_infix(operator, f64::from(l), r),
+        (Value::Double(l), Value::Int(r)) => eval_double_infix(operator, l, f64::from(r)),
+        (Value::Bool(l), Value::Bool(r)) => match operator {
+            "==" => Ok(Value::Bool(l == r)),
+            "!=" => Ok(Value::Bool(l != r)),
+            "&" => Ok(Value::Bool(l & r)),
+            "|" => Ok(Value::Bool(l | r)),
+            "^" => Ok(Value::Bool(l ^ r)),
+            _ => EvalError::throw(format!("unknown operator: boolean {} boolean", operator)),
+        },
+        (Value::Str(l), Value::Str(r)) => match operator {
+            "+" => Ok(Value::Str(l + &r)),
+            "==" => Ok(Value::Bool(l == r)),
+            "!=" => Ok(Value::Bool(l != r)),
+            _ => EvalError::throw(format!("unknown operator: string {} string", operator)),
+        },
+        (left, right) => match operator {
+            "==" => Ok(Value::Bool(left == right)),
+            "!=" => Ok(Value::Bool(left != right)),
+            _ => EvalError::throw(format!(
+                "type mismatch: {} {} {}",
+                left.value_type(),
+                operator,
+                right.value_type()
+            )),
+        },
+    }
+}
+
+// IEEE 754 に従う（0 除算は inf や NaN になり、NaN はどの値とも等しくない）
+fn eval_double_infix(operator: &str, l: f64, r: f64) -> Result<Value, EvalError> {
+    let result = match operator {
+        "+" => l + r,
+        "-" => l - r,
+        "*" => l * r,
+        "/" => l / r,
+        "%" => l % r,
+        "**" => l.powf(r),
+        "==" => return Ok(Value::Bool(l == r)),
+        "!=" => return Ok(Value::Bool(l != r)),
+        "<" => return Ok(Value::Bool(l < r)),
+        ">" => return Ok(Value::Bool(l > r)),
+        "<=" => return Ok(Value::Bool(l <= r)),
+        ">=" => return Ok(Value::Bool(l >= r)),
+        _ => return EvalError::throw(format!("unknown operator: double {} double", operator)),
+    };
+    Ok(Value::Double(result))
+}
+
+// 文字列は文字（char）単位で数える
+fn eval_index(left: Value, index: Value) -> Result<Value, EvalError> {
+    match (left, index) {
+        (Value::Hash(hash), key) => {
+            let key = hash_key(key)?;
+            match hash.borrow().get(&key) {
+                Some(value) => Ok(value.clone()),
+                None => EvalError::throw(format!("key not found: {}", key)),
+            }
+        }
+        (Value::Array(elements), Value::Int(i)) => {
+            let elements = elements.borrow();
+            let i = normalize_index(i, elements.len())?;
+            Ok(elements[i].clone())
+        }
+        (Value::Str(s), Value::Int(i)) => {
+            let i = normalize_index(i, s.chars().count())?;
+            Ok(Value::Str(s.chars().nth(i).unwrap().to_string()))
+        }
+        (left, index) => EvalError::throw(format!(
+            "index operator not supported: {}[{}]",
+            left.value_type(),
+            index.value_type()
+        )),
+    }
+}
+
+// a[i] = v。文字列は書き換えられない
+fn assign_index(container: Value, index: Value, value: Value) -> Result<(), EvalError> {
+    match (container, index) {
+        (Value::Array(elements), Value::Int(i)) => {
+            let mut elements = elements.borrow_mut();
+            let i = normalize_index(i, elements.len())?;
+            elements[i] = value;
+            Ok(())
+        }
+        (Value::Hash(hash), key) => {
+            hash.borrow_mut().insert(hash_key(key)?, value);
+            Ok(())
+        }
+        (container, index) => EvalError::throw(format!(
+            "index assignment not supported: {}[{}]",
+            container.value_type(),
+            index.value_type()
+        )),
+    }
+}
+
+// for で順に束縛する値。変数が 2 つなら配列・文字列・範囲は (添字, 要素)、ハッシュは (キー, 値)
+// 変数が 1 つなら要素（ハッシュはキー）
+// 配列やハッシュはループ中に元が変わっても影響しないように先に取り出す。範囲は順に作る
+fn iteration_items(
+    iterable: Value,
+    arity: usize,
+) -> Result<Box<dyn Iterator<Item = Vec<Value>>>, EvalError> {
+    let elements: Box<dyn Iterator<Item = Value>> = match iterable {
+        Value::Range {
+            start,
+            end,
+            inclusive,
+        } => {
+            let end = i64::from(end) + i64::from(inclusive);
+            Box::new((i64::from(start)..end).map(|i| Value::Int(i as i32)))
+        }
+        Value::Array(elements) => Box::new(elements.borrow().to_vec().into_iter()),
+        Value::Str(s) => Box::new(
+            s.chars()
+                .map(|c| Value::Str(c.to_string()))
+                .collect::<Vec<Value>>()
+                .into_iter(),
+        ),
+        Value::Hash(hash) => {
+            let entries = hash.borrow().iter().cloned().collect::<Vec<_>>();
+            let items = entries.into_iter().map(move |(key, value)| match arity {
+                1 => vec![Value::from(key)],
+                _ => vec![Value::from(key), value],
+            });
+            return Ok(Box::new(items));
+        }
+        other => {
+            return EvalError::throw(format!("cannot iterate over {}", other.value_type()));
+        }
+    };
+
+    let items = elements.enumerate().map(move |(i, element)| match arity {
+        1 => vec![element],
+        _ => vec![Value::Int(i as i32), element],
+    });
+    Ok(Box::new(items))
+}
+
+fn hash_key(value: Value) -> Result<HashKey, EvalError> {
+    HashKey::try_from(value).or_else(|value| {
+        EvalError::throw(format!(
+            "unhashable key: {} (keys must be int, boolean or string)",
+            value.value_type()
+        ))
+    })
+}
+
+// 負の添字は末尾から数える（-1 が最後の要素）
+fn resolve_index(index: i32, len: usize) -> Option<usize> {
+    if index < 0 {
+        len.checked_sub(index.unsigned_abs() as usize)
+    } else {
+        Some(index as usize)
+    }
+}
+
+fn normalize_index(index: i32, len: usize) -> Result<usize, EvalError> {
+    match resolve_index(index, len) {
+        Some(i) if i < len => Ok(i),
+        _ => EvalError::throw(format!("index out of range: {} (length {})", index, len)),
+    }
+}
+
+// start <= end <= len でなければエラー（範囲外を切り詰めたりはしない）
+fn slice_range(
+    start: Option<i32>,
+    end: Option<i32>,
+    len: usize,
+) -> Result<Range<usize>, EvalError> {
+    let from = start.map_or(Some(0), |i| resolve_index(i, len));
+    let to = end.map_or(Some(len), |i| resolve_index(i, len));
+    match (from, to) {
+        (Some(from), Some(to)) if from <= to && to <= len => Ok(from..to),
+        _ => {
+            let bound = |b: Option<i32>| b.map_or(String::new(), |b| b.to_string());
+            EvalError::throw(format!(
+                "slice out of range: [{}:{}] (length {})",
+                bound(start),
+                bound(end),
+                len
+            ))
+        }
+    }
+}
+
+fn eval_integer_infix(operator: &str, l: i32, r: i32) -> Result<Value, EvalError> {
+    let result = match operator {
+        "+" => l.checked_add(r),
+        "-" => l.checked_sub(r),
+        "*" => l.checked_mul(r),
+        "/" | "%" if r == 0 => return EvalError::throw("division by zero".to_string()),
+        "/" => l.checked_div(r),
+        "%" => l.checked_rem(r),
+        "**" => match u32::try_from(r) {
+            Ok(r) => l.checked_pow(r),
+            Err(_) => return EvalError::throw(format!("negative exponent: {} ** {}", l, r)),
+        },
+        "&" => Some(l & r),
+        "|" => Some(l | r),
+        "^" => Some(l ^ r),
+        "<<" | ">>" if !(0..32).contains(&r) => {
+            return EvalError::throw(format!("shift amount out of range: {}", r))
+        }
+        "<<" => Some(l << r),
+        ">>" => Some(l >> r),
+        "==" => return Ok(Value::Bool(l == r)),
+        "!=" => return Ok(Value::Bool(l != r)),
+        "<" => return Ok(Value::Bool(l < r)),
+        ">" => return Ok(Value::Bool(l > r)),
+        "<=" => return Ok(Value::Bool(l <= r)),
+        ">=" => return Ok(Value::Bool(l >= r)),
+        _ => return EvalError::throw(format!("unknown operator: int {} int", operator)),
+    };
+    match result {
+        Some(i) => Ok(Value::Int(i)),
+        None => EvalError::throw(format!("integer overflow: {} {} {}", l, operator, r)),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvalError {
+    pub message: String,
+    pub span: Option<Span>, // エラーを起こした演算子か文の位置
+}
+
+impl EvalError {
+    fn new(message: String) -> Self {
+        EvalError {
+            message,
+            span: None,
+        }
+    }
+
+    fn at(mut self, span: Span) -> Self {
+        self.span.get_or_insert(span);
+        self
+    }
+
+    fn throw<T>(message: String) -> Result<T, Self> {
+        Err(EvalError::new(message))
+    }
+
+    // どこで起きたエラーかを前に付ける
+    fn context(self, context: &str) -> Self {
+        EvalError {
+            message: format!("{}: {}", context, self.message),
+            ..self
+        }
+    }
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.span {
+            Some(span) => write!(f, "EvalError at {}: {}", span, self.message),
+            None => write!(f, "EvalError: {}", self.message),
+        }
+    }
+}
+
+impl error::Error for EvalError {}
+
+#[cfg(test)]
+mod tests {
+    use crate::evaluator::Evaluator;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::value::Value;
+
+    fn eval(input: &str) -> Result<Value, String> {
+        let result = Parser::new(Lexer::new(input)).parse_program();
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        Evaluator::new()
+            .eval_program(&result.program)
+            .map_err(|err| err.message)
+    }
+
+    #[test]
+    fn integer_arithmetic() {
+        let tests = [
+            ("1 + 2 * 3", 7),
+            ("(1 + 2) * 3", 9),
+            ("-5 + 10", 5),
+            ("7 % 3", 1),
+            ("2 ** 3 ** 2", 512),
+            ("1 << 4 | 1", 17),
+            ("1_000 / 3", 333),
+            ("let x = 2; x += 3; x", 5),
+            ("let add = fn (a, b) { return a + b; }; add(2, 3)", 5),
+            (
+                "let f = fn (n) { if (n == 0) { return 0; } else { return f(n - 1) + 1; } }; f(5000)",
+                5000,
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(eval(input), Ok(Value::Int(expected)), "{}", input);
+        }
+    }
+
+    #[test]
+    fn logical_operator() {
+        let tests = [
+            ("true && false", false),
+            ("true || false", true),
+            ("1 < 2 && 2 < 3", true),
+            ("false || 1 == 1 && false", false),
+            ("!true || !false", true),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(eval(input), Ok(Value::Bool(expected)), "{}", input);
+        }
+    }
+
+    #[test]
+    fn short_circuit() {
+        // 右辺が評価されればエラーや副作用が出る
+        let tests = [
+            ("false && 1 / 0 == 0", false),
+            ("true || undefined", true),
+            (
+                "let n = 0; let f = fn () { n += 1; true }; false && f(); n == 0",
+                true,
+            ),
+            (
+                "let n = 0; let f = fn () { n += 1; true }; true && f(); n == 1",
+                true,
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(eval(input), Ok(Value::Bool(expected)), "{}", input);
+        }
+    }
+
+    #[test]
+    fn double_arithmetic() {
+        let tests = [
+            ("1.5 + 2.25", "3.75"),
+            ("1 + 0.5", "1.5"),
+            ("0.5 * 4", "2.0"),
+            ("7 / 2.0", "3.5"),
+            ("7 / 2", "3"),
+            ("-2.5", "-2.5"),
+            ("2.0 ** 0.5 * 2.0 ** 0.5 > 1.99", "true"),
+            ("1 == 1.0", "true"),
+            ("1 < 1.5 && 2.5 >= 2", "true"),
+            ("6.02e23", "6.02e23"),
+            ("let x = 1.0; x += 1; x", "2.0"),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(eval(input).unwrap().to_string(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn double_nan_and_infinity() {
+        let tests = [
+            ("1.0 / 0", "inf"),
+            ("-1 / 0.0", "-inf"),
+            ("0.0 / 0.0", "NaN"),
+            ("1.0 / 0 - 1.0 / 0", "NaN"),
+            ("1.0 / 0 > 1e308", "true"),
+            ("let nan = 0.0 / 0.0; nan == nan", "false"),
+            ("let nan = 0.0 / 0.0; nan != nan", "true"),
+            ("let nan = 0.0 / 0.0; nan < 1 || nan >= 1", "false"),
+            ("1 % 0.0", "NaN"),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(eval(input).unwrap().to_string(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn string_operation() {
+        let tests = [
+            (
+                r#""hello" + ", " + "world""#,
+                Value::Str("hello, world".into()),
+            ),
+            (r#"let s = "abc"; s[1]"#, Value::Str("b".into())),
+            (r#""日本語"[2]"#, Value::Str("語".into())),
+            (r#""a" + "b" == "ab""#, Value::Bool(true)),
+            (r#""a" != "a""#, Value::Bool(false)),
+            (r#""a\tb""#, Value::Str("a\tb".into())),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(eval(input), Ok(expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn array() {
+        let tests = [
+            ("[1, 2 + 3, true]", "[1, 5, true]"),
+            (r#"["a", "b\"c"]"#, r#"["a", "b\"c"]"#),
+            ("[]", "[]"),
+            ("let a = [1, 2, 3]; a[0] + a[2]", "4"),
+            ("let a = [1, 2, 3]; a[-1]", "3"),
+            ("[[1, 2], [3]][0][1]", "2"),
+            ("let a = [1, 2, 3, 4]; a[1:3]", "[2, 3]"),
+            ("let a = [1, 2, 3, 4]; a[:2]", "[1, 2]"),
+            ("let a = [1, 2, 3, 4]; a[2:]", "[3, 4]"),
+            ("let a = [1, 2, 3, 4]; a[-3:-1]", "[2, 3]"),
+            ("let a = [1, 2, 3, 4]; a[:]", "[1, 2, 3, 4]"),
+            ("[1, 2][2:]", "[]"),
+            ("[1, [2]] == [1, [2]]", "true"),
+            (r#""hello"[-1]"#, "o"),
+            (r#""hello"[1:-1]"#, "ell"),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(eval(input).unwrap().to_string(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn hash() {
+        let tests = [
+            (
+                r#"{"name": "moca", 1: true, false: [1]}"#,
+                r#"{"name": "moca", 1: true, false: [1]}"#,
+            ),
+            ("{}", "void"),
+            ("let h = {}; h", "{}"),
+            (
+                r#"let h = {"b": 1, "a": 2, "c": 3}; h"#,
+                r#"{"b": 1, "a": 2, "c": 3}"#,
+            ),
+            (
+                r#"let h = {"a": 1, "b": 2, "a": 3}; h"#,
+                r#"{"a": 3, "b": 2}"#,
+            ),
+            (r#"let h = {"name": "moca", 1: true}; h["name"]"#, "moca"),
+            ("let h = {1: true}; h[1]", "true"),
+            (r#"let k = "x"; let h = {k + "y": 1}; h["xy"]"#, "1"),
+            ("{1: 2} == {1: 2}", "true"),
+            (r#"{1: 2, "a": 3} == {"a": 3, 1: 2}"#, "true"),
+            ("{1: 2} == {1: 3}", "false"),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(eval(input).unwrap().to_string(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn while_loop() {
+        let tests = [
+            ("let i = 0; let s = 0; while (i < 5) { i += 1; s += i; } s", "15"),
+            ("let i = 0; while (true) { i += 1; if (i == 3) { break; } } i", "3"),
+            (
+                "let i = 0; let s = 0; while (i < 5) { i += 1; if (i % 2 == 0) { continue; } s += i; } s",
+                "9",
+            ),
+            ("while (false) { 1 }", "void"),
+            (
+                "let i = 0; let n = 0;
+                'outer: while (i < 3) {
+                    i += 1;
+                    let j = 0;
+                    while (true) {
+                        j += 1;
+                        if (j > i) { continue 'outer; }
+                        if (i == 3) { break 'outer; }
+                        n += 1;
+                    }
+                }
+                n",
+                "3",
+            ),
+            (
+                "let f = fn () { let i = 0; while (true) { i += 1; if (i == 4) { return i * 10; } } };
+                f()",
+                "40",
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(eval(input).unwrap().to_string(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn for_loop() {
+        let tests = [
+            ("let s = 0; for i in 0..5 { s += i; } s", "10"),
+            ("let s = 0; for i in 1..=5 { s += i; } s", "15"),
+            ("let s = 0; for i in 5..0 { s += 1; } s", "0"),
+            ("let s = 0; for x in [1, 2, 3] { s += x * x; } s", "14"),
+            ("let s = 0; for i, x in [5, 6] { s += i * x; } s", "6"),
+            (r#"let s = ""; for c in "abc" { s = c + s; } s"#, "cba"),
+            (
+                r#"let s = ""; for k, v in {"a": "x", "b": "y"} { s += k + ":" + v + ","; } s"#,
+                "a:x,b:y,",
+            ),
+            (r#"let n = 0; for k in {"a": 1, "b": 2} { n += 1; } n"#, "2"),
+            (
+                "let s = 0; for i in 0..10 { if (i == 5) { break; } if (i % 2 == 0) { continue; } s += i; } s",
+                "4",
+            ),
+            (
+                "let n = 0; 'outer: for i in 0..3 { for j in 0..3 { if (j == 1) { continue 'outer; } n += 1; } } n",
+                "3",
+            ),
+            ("0..=3", "0..=3"),
+            ("let n = 0; for i in 2147483646..=2147483647 { n += 1; } n", "2"),
+            ("for i in 0..2147483647 { if (i == 3) { return i; } }", "3"),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(eval(input).unwrap().to_string(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn assignment() {
+        let tests = [
+            ("let x = 1; x = x + 1; x", "2"),
+            ("let x = 1; x = 5", "5"),
+            ("let a = 0; let b = 0; a = b = 3; a + b", "6"),
+            ("let x = 1; { x = 2; } x", "2"),
+            ("let x = 1; { let x = 5; x = 6; } x", "1"),
+            ("let x = 0; let f = fn () { x = 10; }; f(); x", "10"),
+            ("let a = [1, 2, 3]; a[0] = 9; a[-1] += 1; a", "[9, 2, 4]"),
+            ("let a = [1, 2]; let b = a; b[0] = 5; a", "[5, 2]"),
+            (
+                r#"let h = {"a": 1}; h["b"] = 2; h["a"] *= 10; h"#,
+                r#"{"a": 10, "b": 2}"#,
+            ),
+            (
+                "let m = [[0, 0], [0, 0]]; m[1][0] = 7; m",
+                "[[0, 0], [7, 0]]",
+            ),
+            // 自分自身を含む値も表示・比較できる
+            ("let a = [1]; a[0] = a; a", "[[...]]"),
+            (r#"let h = {}; h["self"] = h; h"#, r#"{"self": {...}}"#),
+            ("let a = [1, 2]; a[1] = [a]; a", "[1, [[...]]]"),
+            ("let a = [1]; a[0] = a; a == a", "true"),
+            (
+                "let a = [1]; let b = [1]; a[0] = b; b[0] = a; a == b",
+                "true",
+            ),
+            (
+                "let a = [1, 2]; let b = [1, 3]; a[0] = b; b[0] = a; a == b",
+                "false",
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(eval(input).unwrap().to_string(), expected, "{}", input);
+        }
+    }
+
+    // 深く入れ子になった値も比較・表示・解放でスタックを使い切らない
+    #[test]
+    fn deeply_nested_value() {
+        let nest = "let a = []; let b = []; for i in 0..50000 { a = [a]; b = [b]; }";
+        assert_eq!(eval(&format!("{} a == b", nest)), Ok(Value::Bool(true)));
+        assert_eq!(eval(&format!("{} a = 0; 1", nest)), Ok(Value::Int(1)));
+        let a = eval(&format!("{} a", nest)).unwrap().to_string();
+        assert_eq!(a.len(), 2 * 50_001);
+        assert!(a.starts_with("[[[") && a.ends_with("]]]"));
+
+        let nest = "let h = {}; for i in 0..50000 { h = {\"h\": h}; }";
+        assert_eq!(eval(&format!("{} h = 0; 1", nest)), Ok(Value::Int(1)));
+    }
+
+    #[test]
+    fn typed_declaration() {
+        let tests = [
+            ("int x = 1; x + 1", "2"),
+            ("double d = 1; d", "1.0"),
+            ("let d: double = 3 / 2; d", "1.0"),
+            ("boolean b = 1 < 2; b", "true"),
+            ("let f = fn () { 2 }; int n = f(); n", "2"),
+            // 宣言した型は代入でも守られる
+            ("int x = 1; x = 5; x += 2; x", "7"),
+            ("double d = 1.5; d = 2; d", "2.0"),
+            ("int x = 1; { x = 3; } x", "3"),
+            ("int x = 1; let x = true; x = false; x", "false"),
+            ("int x = 1; { let x = true; x = false; } x", "1"),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(eval(input).unwrap().to_string(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn typed_function() {
+        let tests = [
+            ("let f = fn (int a, int b) -> int { a + b }; f(1, 2)", "3"),
+            ("let f = fn (x: double): double { x / 2 }; f(3)", "1.5"),
+            ("let f = fn (a, b: boolean) { b }; f(1, true)", "true"),
+            ("let f = fn () -> void { }; f()", "void"),
+            (
+                "let a = [0]; fn set(int v) -> void { a[0] = v } set(3); a",
+                "[3]",
+            ),
+            ("fn f(): void { 1; } f()", "void"),
+            ("fn f() -> void { if (true) { return; } 1 } f()", "void"),
+            ("fn f() { return; } f()", "void"),
+            ("let f = fn () { return }; f()", "void"),
+            ("let f = fn (n: int) -> int { return n * 2; }; f(4)", "8"),
+            ("fn (a: int) -> int { a }", "fn (a: int) -> int {\na\n}"),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(eval(input).unwrap().to_string(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn function_declaration() {
+        let tests = [
+            ("fn add(a, b) { a + b } add(1, 2)", "3"),
+            ("let x = twice(4); fn twice(n) { n * 2 } x", "8"),
+            (
+                "fn is_even(int n) -> boolean { if (n == 0) { true } else { is_odd(n - 1) } }
+                fn is_odd(int n) -> boolean { if (n == 0) { false } else { is_even(n - 1) } }
+                is_even(10) && is_odd(7)",
+                "true",
+            ),
+            ("fn outer() { fn inner() { 41 } inner() + 1 } outer()", "42"),
+            (
+                "fn outer() { let x = inner(); fn inner() { 41 } x + 1 } outer()",
+                "42",
+            ),
+            ("fn f(a: int) -> int { a } f", "fn f(a: int) -> int {\na\n}"),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(eval(input).unwrap().to_string(), expected, "{}", input);
+        }
+    }
+
+    // エラーはいちばん内側の演算子か文の位置を持つ
+    #[test]
+    fn eval_error_span() {
+        let tests = [
+            (
+                "let x = 1;\nlet y = x +\n  2 / 0;",
+                "EvalError at 3:5: division by zero",
+            ),
+            (
+                "let a = [1];\n  a[3]",
+                "EvalError at 2:3: index out of range: 3 (length 1)",
+            ),
+            (
+                "let f = fn () {\n  -true\n};\nf()",
+                "EvalError at 2:3: unknown operator: -boolean",
+            ),
+            (
+                "int x = 1;\nx = true",
+                "EvalError at 2:3: mismatched types: expected int, found boolean",
+            ),
+            (
+                "while (true) {\n  y;\n}",
+                "EvalError at 2:3: undefined variable: y",
+            ),
+        ];
+        for (input, expected) in tests {
+            let result = Parser::new(Lexer::new(input)).parse_program();
+            let err = Evaluator::new().eval_program(&result.program).unwrap_err();
+            assert_eq!(err.to_string(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn eval_error() {
+        let tests = [
+            ("1 / 0", "division by zero"),
+            ("2147483647 + 1", "integer overflow: 2147483647 + 1"),
+            ("1 && true", "expected boolean operand for &&, got int"),
+            ("while (1) { }", "expected boolean operand for while, got int"),
+            ("for x in 1 { }", "cannot iterate over int"),
+            ("y = 1", "undefined variable: y"),
+            ("int x = 1.5 * 2;", "mismatched types: expected int, found double"),
+            (
+                "let x: int = 1; x = true; x",
+                "mismatched types: expected int, found boolean",
+            ),
+            ("int x = 1; x += 0.5", "mismatched types: expected int, found double"),
+            (
+                "int x = 1; let f = fn () { x = [x]; }; f()",
+                "mismatched types: expected int, found array",
+            ),
+            (
+                "let f = fn (int a) { a = true; }; f(1)",
+                "mismatched types: expected int, found boolean",
+            ),
+            ("{ fn f() { 1 } } f()", "undefined variable: f"),
+            (
+                "let f = fn (int a) { a }; f(true)",
+                "argument a: mismatched types: expected int, found boolean",
+            ),
+            (
+                "let f = fn () -> int { }; f()",
+                "return value: mismatched types: expected int, found void",
+            ),
+            (
+                "let f = fn (x) -> boolean { return x; }; f(1)",
+                "return value: mismatched types: expected boolean, found int",
+            ),
+            (
+                "fn f() -> void { return 1; } f()",
+                "return value: mismatched types: expected void, found int",
+            ),
+            (
+                "fn f() -> int { return; } f()",
+                "return value: mismatched types: expected int, found void",
+            ),
+            ("let x = [1]; boolean b = x[0];", "mismatched types: expected boolean, found int"),
+            ("let a = [1]; a[1] = 2", "index out of range: 1 (length 1)"),
+            (r#"let s = "ab"; s[0] = "c""#, "index assignment not supported: string[int]"),
+            (r#"let h = {}; h["x"] += 1"#, r#"key not found: "x""#),
+            ("0..1.5", "range bounds must be int, got int..double"),
+            (r#""a" + 1"#, "type mismatch: string + int"),
+            ("1.5 & 1", "unknown operator: double & double"),
+            (r#""a" - "b""#, "unknown operator: string - string"),
+            (r#""abc"[3]"#, "index out of range: 3 (length 3)"),
+            ("1[0]", "index operator not supported: int[int]"),
+            ("[1, 2][2]", "index out of range: 2 (length 2)"),
+            ("[1, 2][-3]", "index out of range: -3 (length 2)"),
+            ("[][0]", "index out of range: 0 (length 0)"),
+            ("[1, 2][true]", "index operator not supported: array[boolean]"),
+            ("[1, 2, 3][2:1]", "slice out of range: [2:1] (length 3)"),
+            ("[1, 2, 3][:4]", "slice out of range: [:4] (length 3)"),
+            ("[1, 2, 3][-4:]", "slice out of range: [-4:] (length 3)"),
+            ("[1][0.5:]", "slice index must be int, got double"),
+            ("1[:]", "slice operator not supported: int"),
+            (r#"let h = {"a": 1}; h["b"]"#, r#"key not found: "b""#),
+            (
+                "let h = {[1]: 1};",
+                "unhashable key: array (keys must be int, boolean or string)",
+            ),
+            (
+                "let h = {1: 1}; h[1.0]",
+                "unhashable key: double (keys must be int, boolean or string)",
+            ),
+            ("true && 1", "expected boolean operand for &&, got int"),
+            ("x", "undefined variable: x"),
+            (
+                "let f = fn (a) { a }; f()",
+                "wrong number of arguments: expected 1, got 0",
+            ),
+            (
+                "let f = fn (n) { if (n == 0) { return 0; } else { return f(n - 1) + 1; } }; f(10000)",
+                "maximum call depth exceeded",
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(eval(input), Err(expected.to_string()), "{}", input);
+        }
+    }
+}
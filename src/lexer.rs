@@ -1,24 +1,168 @@
-use crate::token::{Token, TokenKind};
-use std::{collections::VecDeque, str, vec};
+use crate::token::{Span, Token, TokenKind};
+use core::fmt;
+use std::{borrow::Cow, error};
+use unicode_xid::UnicodeXID;
 
-pub struct Lexer {
-    chars: VecDeque<char>,
+#[derive(Debug, Clone, PartialEq)]
+pub enum LexErrorKind {
+    UnterminatedString,      // 閉じる " がない
+    InvalidCharacter(char),  // 言語で使えない文字
+    MalformedNumber(String), // 数値として解釈できない並び
+    InvalidEscape(String),   // 文字列中の不正なエスケープ
+    UnterminatedComment,     // 閉じる */ がない
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LexError {
+    pub kind: LexErrorKind,
+    pub span: Span,
+}
+
+impl LexErrorKind {
+    // エラーの種類ごとの固定のコード
+    pub fn code(&self) -> &'static str {
+        match self {
+            LexErrorKind::UnterminatedString => "L0001",
+            LexErrorKind::InvalidCharacter(_) => "L0002",
+            LexErrorKind::MalformedNumber(_) => "L0003",
+            LexErrorKind::InvalidEscape(_) => "L0004",
+            LexErrorKind::UnterminatedComment => "L0005",
+        }
+    }
+}
+
+impl fmt::Display for LexErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LexErrorKind::UnterminatedString => write!(f, "unterminated string literal"),
+            LexErrorKind::InvalidCharacter(c) => write!(f, "invalid character {:?}", c),
+            LexErrorKind::MalformedNumber(n) => write!(f, "malformed number {:?}", n),
+            LexErrorKind::InvalidEscape(e) => write!(f, "invalid escape sequence {:?}", e),
+            LexErrorKind::UnterminatedComment => write!(f, "unterminated block comment"),
+        }
+    }
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "LexError at {}: {}", self.span, self.kind)
+    }
+}
+
+impl error::Error for LexError {}
+
+// 記号とトークンの対応。長いものから順に一致を試す
+const SYMBOLS: [(&str, TokenKind); 38] = [
+    ("..=", TokenKind::DotDotEqual),
+    ("**", TokenKind::Power),
+    ("<<", TokenKind::ShiftLeft),
+    (">>", TokenKind::ShiftRight),
+    ("<=", TokenKind::LessEqual),
+    (">=", TokenKind::GreaterEqual),
+    ("==", TokenKind::Equal),
+    ("!=", TokenKind::NotEqual),
+    ("&&", TokenKind::And),
+    ("||", TokenKind::Or),
+    ("+=", TokenKind::PlusAssign),
+    ("-=", TokenKind::MinusAssign),
+    ("*=", TokenKind::AsteriskAssign),
+    ("/=", TokenKind::SlashAssign),
+    ("..", TokenKind::DotDot),
+    ("->", TokenKind::Arrow),
+    ("+", TokenKind::Plus),
+    ("-", TokenKind::Minus),
+    ("*", TokenKind::Asterisk),
+    ("/", TokenKind::Slash),
+    ("%", TokenKind::Percent),
+    ("&", TokenKind::BitAnd),
+    ("|", TokenKind::BitOr),
+    ("^", TokenKind::BitXor),
+    ("!", TokenKind::Bang),
+    ("=", TokenKind::Assign),
+    ("<", TokenKind::LessThan),
+    (">", TokenKind::GreaterThan),
+    ("(", TokenKind::Lparen),
+    (")", TokenKind::Rparen),
+    ("{", TokenKind::Lcurly),
+    ("}", TokenKind::Rcurly),
+    ("[", TokenKind::Lsquare),
+    ("]", TokenKind::Rsquare),
+    (";", TokenKind::SemiColon),
+    (":", TokenKind::Colon),
+    (",", TokenKind::Comma),
+    (".", TokenKind::Dot),
+];
+
+// ソースを複製せず、バイト位置で読み進める
+// トークンの値は可能な限りソースのスライスを借用する
+#[derive(Clone)]
+pub struct Lexer<'src> {
+    src: &'src str,
     current: Option<char>,
+    offset: usize,  // current のバイトオフセット
+    line: usize,    // current の行（1始まり）
+    column: usize,  // current の列（1始まり）
+    lossless: bool, // 空白やコメントもトークンとして返すかどうか
+    finished: bool, // Iterator として Eof を返し終えたかどうか
 }
 
-impl Lexer {
-    pub fn new(src: &str) -> Self {
-        let mut chars: VecDeque<char> = src.chars().collect();
-        let current = chars.pop_front();
-        Lexer { chars, current }
+impl<'src> Lexer<'src> {
+    pub fn new(src: &'src str) -> Self {
+        Lexer {
+            src,
+            current: src.chars().next(),
+            offset: 0,
+            line: 1,
+            column: 1,
+            lossless: false,
+            finished: false,
+        }
+    }
+
+    // 空白やコメントも Whitespace / Comment トークンとして返す Lexer を作る
+    // 返されたトークンの text をすべてつなげると元のソースに戻る
+    pub fn lossless(src: &'src str) -> Self {
+        Lexer {
+            lossless: true,
+            ..Lexer::new(src)
+        }
     }
 
     fn read_char(&mut self) {
-        self.current = self.chars.pop_front();
+        if let Some(c) = self.current {
+            self.offset += c.len_utf8();
+            if c == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+        }
+        self.current = self.rest().chars().next();
+    }
+
+    // current 以降のまだ読んでいないソース
+    fn rest(&self) -> &'src str {
+        &self.src[self.offset..]
     }
 
     fn peek_char(&self) -> Option<char> {
-        self.chars.front().map(&char::to_owned)
+        self.rest().chars().nth(1)
+    }
+
+    // current から始まる文字列が pattern と一致するかどうか
+    fn starts_with(&self, pattern: &str) -> bool {
+        self.rest().starts_with(pattern)
+    }
+
+    // current が /// の先頭かどうか（//// は通常のコメント）
+    fn at_doc_comment(&self) -> bool {
+        self.starts_with("///") && !self.starts_with("////")
+    }
+
+    // current が """ の先頭かどうか
+    fn at_triple_quote(&self) -> bool {
+        self.starts_with("\"\"\"")
     }
 
     fn skip_while(&mut self, test: impl Fn(char) -> bool) {
@@ -31,181 +175,382 @@ impl Lexer {
         }
     }
 
-    fn get_while(&mut self, test: impl Fn(char) -> bool) -> String {
-        let mut chars = Vec::new();
-        while let Some(c) = self.current {
-            if test(c) {
-                chars.push(c);
-                self.read_char()
-            } else {
-                break;
-            }
+    fn get_while(&mut self, test: impl Fn(char) -> bool) -> &'src str {
+        let start = self.offset;
+        self.skip_while(test);
+        &self.src[start..self.offset]
+    }
+
+    // n 文字読む
+    fn get_n(&mut self, n: usize) -> &'src str {
+        let start = self.offset;
+        for _ in 0..n {
+            self.read_char();
         }
-        chars.iter().collect()
+        &self.src[start..self.offset]
     }
 
-    pub fn next_token(&mut self) -> Option<Token> {
-        self.skip_while(char::is_whitespace);
-        self.skip_comment();
-        self.skip_while(char::is_whitespace);
+    // 入力の終わりでは Eof を返す（何度呼んでも Eof）
+    // エラーの場合も問題の文字までは読み進めるので、続けて呼び出すことができる
+    pub fn next_token(&mut self) -> Result<Token<'src>, LexError> {
+        if !self.lossless {
+            self.skip_trivia()?;
+        }
 
-        self.current.and_then(|c| {
-            if c.is_numeric() {
-                Some(self.find_numeric())
-            } else if Self::is_symbol(c) {
-                Some(self.find_symbol())
-            } else if c.is_alphabetic() {
-                Some(self.find_word())
-            } else if c == '"' {
-                Some(self.find_string())
-            } else {
-                self.read_char();
-                Some(Token::new(TokenKind::Other, c.to_string()))
-            }
-        })
+        let (start, line, column) = (self.offset, self.line, self.column);
+        let Some(c) = self.current else {
+            let span = Span::new(start, start, line, column);
+            return Ok(Token::new(TokenKind::Eof, "", span));
+        };
+
+        let found = if self.at_doc_comment() {
+            Ok(self.find_doc_comment())
+        } else if c.is_whitespace() {
+            Ok((
+                TokenKind::Whitespace,
+                self.get_while(char::is_whitespace).into(),
+            ))
+        } else if self.starts_with("//") {
+            Ok((
+                TokenKind::Comment,
+                self.get_while(|c| !Self::is_end(c)).into(),
+            ))
+        } else if self.starts_with("/*") {
+            Ok((TokenKind::Comment, self.read_block_comment()?.into()))
+        } else if c.is_ascii_digit() {
+            self.find_numeric()
+        } else if Self::is_symbol(c) {
+            self.find_symbol()
+        } else if c == 'r' && self.peek_char() == Some('"') {
+            self.find_raw_string()
+        } else if Self::is_ident_start(c) {
+            Ok(self.find_word())
+        } else if c == '\'' && self.peek_char().is_some_and(Self::is_ident_start) {
+            Ok(self.find_label())
+        } else if c == '"' {
+            self.find_string()
+        } else {
+            self.read_char();
+            Err(LexErrorKind::InvalidCharacter(c))
+        };
+
+        let span = Span::new(start, self.offset, line, column);
+        match found {
+            Ok((kind, value)) => Ok(Token::new(kind, value, span)),
+            Err(kind) => Err(LexError { kind, span }),
+        }
     }
 
     fn is_symbol(c: char) -> bool {
-        vec![
-            '=', '+', '-', '*', '/', '!', '&', '|', ';', '(', ')', '{', '}', '[', ']', ':', ',',
-            '<', '>',
+        [
+            '=', '+', '-', '*', '/', '%', '!', '&', '|', '^', ';', '(', ')', '{', '}', '[', ']',
+            ':', ',', '.', '<', '>',
         ]
         .contains(&c)
     }
 
+    // 識別子の先頭に使える文字（Unicode の XID_Start と _）
+    fn is_ident_start(c: char) -> bool {
+        c == '_' || c.is_xid_start()
+    }
+
+    // 識別子の 2 文字目以降に使える文字（Unicode の XID_Continue）
+    fn is_ident_continue(c: char) -> bool {
+        c.is_xid_continue()
+    }
+
     fn is_end(c: char) -> bool {
-        vec!['\n', '\r', '\0'].contains(&c)
+        ['\n', '\r', '\0'].contains(&c)
     }
 
-    fn skip_comment(&mut self) {
-        if Some('/') == self.current {
-            match self.peek_char() {
-                Some('/') => {
-                    self.skip_while(|c| !Self::is_end(c));
-                }
-                _ => (),
+    // 空白とコメントを読み飛ばす（/// のドキュメントコメントはトークンとして残す）
+    fn skip_trivia(&mut self) -> Result<(), LexError> {
+        loop {
+            self.skip_while(char::is_whitespace);
+            if self.at_doc_comment() {
+                return Ok(());
+            } else if self.starts_with("//") {
+                self.skip_while(|c| !Self::is_end(c));
+            } else if self.starts_with("/*") {
+                self.read_block_comment()?;
+            } else {
+                return Ok(());
             }
         }
     }
 
-    fn find_symbol(&mut self) -> Token {
-        let op = self.current.unwrap();
-        let mut text = op.to_string();
-        let kind = match op {
-            '+' => TokenKind::Plus,
-            '-' => TokenKind::Minus,
-            '*' => TokenKind::Asterisk,
-            '/' => TokenKind::Slash,
-            '(' => TokenKind::Lparen,
-            ')' => TokenKind::Rparen,
-            '{' => TokenKind::Lcurly,
-            '}' => TokenKind::Rcurly,
-            '[' => TokenKind::Lsquare,
-            ']' => TokenKind::Rsquare,
-            ';' => TokenKind::SemiColon,
-            ':' => TokenKind::Colon,
-            ',' => TokenKind::Comma,
-            '>' => TokenKind::LessThan,
-            '<' => TokenKind::GreaterThan,
-            '=' => match self.peek_char() {
-                Some('=') => {
+    // /* ... */ を読む。入れ子にできる
+    fn read_block_comment(&mut self) -> Result<&'src str, LexError> {
+        let (start, line, column) = (self.offset, self.line, self.column);
+        let mut depth = 0;
+        loop {
+            if self.starts_with("/*") {
+                depth += 1;
+                self.get_n(2);
+            } else if self.starts_with("*/") {
+                depth -= 1;
+                self.get_n(2);
+            } else if self.current.is_some() {
+                self.read_char();
+            } else {
+                return Err(LexError {
+                    kind: LexErrorKind::UnterminatedComment,
+                    span: Span::new(start, self.offset, line, column),
+                });
+            }
+            if depth == 0 {
+                return Ok(&self.src[start..self.offset]);
+            }
+        }
+    }
+
+    fn find_doc_comment(&mut self) -> (TokenKind, Cow<'src, str>) {
+        // /// を飛ばす
+        self.get_n(3);
+        if self.current == Some(' ') {
+            self.read_char();
+        }
+        let value = self.get_while(|c| !Self::is_end(c));
+        (TokenKind::DocComment, value.trim_end().into())
+    }
+
+    fn find_symbol(&mut self) -> Result<(TokenKind, Cow<'src, str>), LexErrorKind> {
+        match SYMBOLS.iter().find(|(symbol, _)| self.starts_with(symbol)) {
+            Some((symbol, kind)) => Ok((*kind, self.get_n(symbol.len()).into())),
+            None => {
+                // & や | 単体などはここに来る
+                let c = self.current.unwrap();
+                self.read_char();
+                Err(LexErrorKind::InvalidCharacter(c))
+            }
+        }
+    }
+
+    fn find_word(&mut self) -> (TokenKind, Cow<'src, str>) {
+        let value = self.get_while(Self::is_ident_continue);
+        let kind = match value {
+            "void" => TokenKind::Void,
+            "return" => TokenKind::Return,
+            "if" => TokenKind::If,
+            "else" => TokenKind::Else,
+            "let" => TokenKind::Let,
+            "fn" => TokenKind::Fn,
+            "while" => TokenKind::While,
+            "break" => TokenKind::Break,
+            "continue" => TokenKind::Continue,
+            "for" => TokenKind::For,
+            "in" => TokenKind::In,
+            "true" => TokenKind::BoolLiteral,
+            "false" => TokenKind::BoolLiteral,
+            "int" => TokenKind::Int,
+            "double" => TokenKind::Double,
+            "boolean" => TokenKind::Boolean,
+            _ => TokenKind::Ident,
+        };
+        (kind, value.into())
+    }
+
+    // 'outer のようなループのラベル
+    fn find_label(&mut self) -> (TokenKind, Cow<'src, str>) {
+        self.read_char(); // '
+        let value = self.get_while(Self::is_ident_continue);
+        (TokenKind::Label, value.into())
+    }
+
+    fn find_string(&mut self) -> Result<(TokenKind, Cow<'src, str>), LexErrorKind> {
+        // """ で始まる場合は """ まで（途中の " や改行はそのまま）
+        let triple = self.at_triple_quote();
+
+        // 始まりの " を飛ばす
+        self.get_n(if triple { 3 } else { 1 });
+
+        let start = self.offset;
+        let end;
+        // エスケープが出てくるまではソースを借用し、出てきたらそこからコピーする
+        let mut escaped: Option<String> = None;
+        // 不正なエスケープがあっても終わりの " までは読み進め、最初のエラーを返す
+        let mut error = None;
+        loop {
+            match self.current {
+                None => return Err(LexErrorKind::UnterminatedString),
+                Some('"') if !triple => {
+                    end = self.offset;
                     self.read_char();
-                    text.push('=');
-                    TokenKind::Equal
+                    break;
                 }
-                _ => TokenKind::Assign,
-            },
-            '!' => match self.peek_char() {
-                Some('=') => {
-                    self.read_char();
-                    text.push('=');
-                    TokenKind::NotEqual
+                Some('"') if self.at_triple_quote() => {
+                    end = self.offset;
+                    self.get_n(3);
+                    break;
                 }
-                _ => TokenKind::Bang,
-            },
-            '&' => match self.peek_char() {
-                Some('&') => {
-                    self.read_char();
-                    text.push('&');
-                    TokenKind::And
+                Some('\\') => {
+                    let value = escaped.get_or_insert_with(|| self.src[start..self.offset].into());
+                    match self.read_escape() {
+                        Ok(c) => value.push(c),
+                        Err(err) => {
+                            error.get_or_insert(err);
+                        }
+                    }
                 }
-                _ => TokenKind::Other,
-            },
-            '|' => match self.peek_char() {
-                Some('|') => {
+                Some(c) => {
+                    if let Some(value) = escaped.as_mut() {
+                        value.push(c);
+                    }
                     self.read_char();
-                    text.push('|');
-                    TokenKind::Or
                 }
-                _ => TokenKind::Other,
-            },
-            _ => TokenKind::Other,
-        };
-        self.read_char();
-        Token::new(kind, text)
-    }
-
-    fn find_word(&mut self) -> Token {
-        let value: String =
-            self.get_while(|c| !(c.is_whitespace() || Self::is_symbol(c) || Self::is_end(c)));
-        match value.as_str() {
-            "void" => Token::new(TokenKind::Void, value),
-            "return" => Token::new(TokenKind::Return, value),
-            "if" => Token::new(TokenKind::If, value),
-            "else" => Token::new(TokenKind::Else, value),
-            "let" => Token::new(TokenKind::Let, value),
-            "fn" => Token::new(TokenKind::Fn, value),
-            "while" => Token::new(TokenKind::While, value),
-            "true" => Token::new(TokenKind::BoolLiteral, value),
-            "false" => Token::new(TokenKind::BoolLiteral, value),
-            "int" => Token::new(TokenKind::Int, value),
-            "double" => Token::new(TokenKind::Double, value),
-            "boolean" => Token::new(TokenKind::Boolean, value),
-            _ => Token::new(TokenKind::Ident, value),
-        }
-    }
-
-    fn find_string(&mut self) -> Token {
-        // 始まりの " を飛ばす
-        self.read_char();
+            }
+        }
+
+        match (error, escaped) {
+            (Some(err), _) => Err(err),
+            (None, Some(value)) => Ok((TokenKind::StringLiteral, value.into())),
+            (None, None) => Ok((TokenKind::StringLiteral, self.src[start..end].into())),
+        }
+    }
 
-        let value: String = self.get_while(|c| c != '"');
+    // r"..." はエスケープを解釈しない
+    fn find_raw_string(&mut self) -> Result<(TokenKind, Cow<'src, str>), LexErrorKind> {
+        // r" を飛ばす
+        self.get_n(2);
+
+        let value = self.get_while(|c| c != '"');
+        if self.current.is_none() {
+            return Err(LexErrorKind::UnterminatedString);
+        }
 
         // 終わりの " を飛ばす
         self.read_char();
 
-        Token::new(TokenKind::StringLiteral, value)
+        Ok((TokenKind::StringLiteral, value.into()))
     }
 
-    fn find_numeric(&mut self) -> Token {
-        let mut stack = Vec::new();
-        let mut dot_flg = false;
+    // current の \ から始まるエスケープシーケンスを読む
+    fn read_escape(&mut self) -> Result<char, LexErrorKind> {
+        self.read_char();
+        let Some(c) = self.current else {
+            return Err(LexErrorKind::UnterminatedString);
+        };
+        self.read_char();
 
-        while let Some(s) = self.current {
-            if s.is_numeric() {
-                stack.push(s);
+        match c {
+            'n' => Ok('\n'),
+            't' => Ok('\t'),
+            'r' => Ok('\r'),
+            '0' => Ok('\0'),
+            '"' => Ok('"'),
+            '\\' => Ok('\\'),
+            'u' => {
+                // \u{XXXX}
+                if self.current != Some('{') {
+                    return Err(LexErrorKind::InvalidEscape("\\u".to_string()));
+                }
                 self.read_char();
-            } else if s == '.' && !dot_flg {
-                dot_flg = true;
-                stack.push(s);
+                let hex = self.get_while(|c| c.is_ascii_hexdigit());
+                let text = format!("\\u{{{}", hex);
+                if self.current != Some('}') {
+                    return Err(LexErrorKind::InvalidEscape(text));
+                }
                 self.read_char();
-            } else {
-                break;
+
+                u32::from_str_radix(hex, 16)
+                    .ok()
+                    .filter(|_| hex.len() <= 6)
+                    .and_then(char::from_u32)
+                    .ok_or(LexErrorKind::InvalidEscape(text + "}"))
             }
+            other => Err(LexErrorKind::InvalidEscape(format!("\\{}", other))),
         }
+    }
+
+    fn find_numeric(&mut self) -> Result<(TokenKind, Cow<'src, str>), LexErrorKind> {
+        let start = self.offset;
+        let radix = match (self.current, self.peek_char()) {
+            (Some('0'), Some('x')) => 16,
+            (Some('0'), Some('o')) => 8,
+            (Some('0'), Some('b')) => 2,
+            _ => 10,
+        };
 
-        let numeric: String = stack.iter().collect();
-        if dot_flg {
-            Token::new(TokenKind::DoubleLiteral, numeric)
+        let mut double = false;
+        let mut malformed = false;
+
+        if radix != 10 {
+            // 0x, 0o, 0b を読む
+            self.get_n(2);
+            let digits = self.get_while(|c| c.is_ascii_alphanumeric() || c == '_');
+            malformed = !digits.chars().any(|c| c.is_digit(radix))
+                || !digits.chars().all(|c| c.is_digit(radix) || c == '_');
         } else {
-            Token::new(TokenKind::IntLiteral, numeric)
+            self.skip_while(|c| c.is_ascii_digit() || c == '_');
+
+            // 小数部（. の後に数字が続く場合のみ）
+            if self.current == Some('.') && self.peek_char().is_some_and(|c| c.is_ascii_digit()) {
+                double = true;
+                self.read_char();
+                self.skip_while(|c| c.is_ascii_digit() || c == '_');
+            }
+
+            // 指数部（6.02e23, 1e-9）
+            if matches!(self.current, Some('e' | 'E')) {
+                double = true;
+                self.read_char();
+                if matches!(self.current, Some('+' | '-')) {
+                    self.read_char();
+                }
+                let exponent = self.get_while(|c| c.is_ascii_digit() || c == '_');
+                malformed = !exponent.chars().any(|c| c.is_ascii_digit());
+            }
+        }
+
+        // 1.2.3 や 123abc のように続いてしまっているものは数値として扱えない
+        // （1.foo のように . の後が数字でなければメンバアクセスの . とする）
+        let trailing = match self.current {
+            Some('.') => self.peek_char().is_some_and(|c| c.is_ascii_digit()),
+            Some(c) => Self::is_ident_continue(c),
+            None => false,
+        };
+        if trailing {
+            malformed = true;
+            self.skip_while(|c| c == '.' || Self::is_ident_continue(c));
+        }
+
+        let numeric = &self.src[start..self.offset];
+        if malformed {
+            Err(LexErrorKind::MalformedNumber(numeric.to_string()))
+        } else if double {
+            Ok((TokenKind::DoubleLiteral, numeric.into()))
+        } else {
+            Ok((TokenKind::IntLiteral, numeric.into()))
+        }
+    }
+}
+
+// Eof まで（Eof を含む）のトークンを順に返す
+// エラーがあっても続きから読み進める
+impl<'src> Iterator for Lexer<'src> {
+    type Item = Result<Token<'src>, LexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
         }
+        let result = self.next_token();
+        self.finished = matches!(&result, Ok(token) if token.is_same_kind(TokenKind::Eof));
+        Some(result)
     }
 }
 
+// ソースをすべてトークンに分割する。最後は必ず Eof
+// 最初に見つかった字句エラーを返す
+pub fn tokenize(src: &str) -> Result<Vec<Token<'_>>, LexError> {
+    Lexer::new(src).collect()
+}
+
 #[cfg(test)]
 mod tests {
-    use super::Lexer;
+    use super::{tokenize, LexErrorKind, Lexer};
+    use crate::token::{Token, TokenKind};
+    use std::borrow::Cow;
 
     #[test]
     fn next_token() {
@@ -214,9 +559,295 @@ mod tests {
         boolean b = true;
         String str = "sss;";
         "#;
-        let mut la = Lexer::new(src);
-        while let Some(token) = la.next_token() {
-            println!("{}", token.to_string());
+        for token in Lexer::new(src).flatten() {
+            println!("{}", token);
+        }
+    }
+
+    #[test]
+    fn token_span() {
+        let src = "let a = 1;\n  a + \"あい\";";
+        let mut spans = Vec::new();
+        for token in tokenize(src).unwrap() {
+            assert_eq!(
+                &src[token.span.start..token.span.end].trim_matches('"'),
+                &token.value
+            );
+            spans.push((token.span.line, token.span.column));
         }
+        assert_eq!(
+            spans,
+            vec![
+                (1, 1),
+                (1, 5),
+                (1, 7),
+                (1, 9),
+                (1, 10),
+                (2, 3),
+                (2, 5),
+                (2, 7),
+                (2, 11),
+                (2, 12)
+            ]
+        );
+    }
+
+    #[test]
+    fn lex_error() {
+        let results: Vec<_> = Lexer::new("a @ b # 1.2.3 12ab 3 \"abc")
+            .map(|result| result.map(|t| t.value.into_owned()).map_err(|e| e.kind))
+            .collect();
+        assert_eq!(
+            results,
+            vec![
+                Ok("a".to_string()),
+                Err(LexErrorKind::InvalidCharacter('@')),
+                Ok("b".to_string()),
+                Err(LexErrorKind::InvalidCharacter('#')),
+                Err(LexErrorKind::MalformedNumber("1.2.3".to_string())),
+                Err(LexErrorKind::MalformedNumber("12ab".to_string())),
+                Ok("3".to_string()),
+                Err(LexErrorKind::UnterminatedString),
+                Ok("".to_string()),
+            ]
+        );
+        assert_eq!(
+            tokenize("a @ #").unwrap_err().kind,
+            LexErrorKind::InvalidCharacter('@')
+        );
+    }
+
+    #[test]
+    fn string_literal() {
+        let src = r#"
+        "a\tb\n\"c\"\\\0\u{3042}" r"C:\path\n" """line1
+"quoted"
+line2""" "" "bad\q escape" "\u{110000}" "#;
+        let results: Vec<_> = Lexer::new(src)
+            .map(|result| result.map(|t| t.value.into_owned()).map_err(|e| e.kind))
+            .collect();
+        assert_eq!(
+            results,
+            vec![
+                Ok("a\tb\n\"c\"\\\0あ".to_string()),
+                Ok(r"C:\path\n".to_string()),
+                Ok("line1\n\"quoted\"\nline2".to_string()),
+                Ok("".to_string()),
+                Err(LexErrorKind::InvalidEscape(r"\q".to_string())),
+                Err(LexErrorKind::InvalidEscape(r"\u{110000}".to_string())),
+                Ok("".to_string()),
+            ]
+        );
+
+        let mut la = Lexer::new(r#""""never closed""#);
+        assert_eq!(
+            la.next_token().unwrap_err().kind,
+            LexErrorKind::UnterminatedString
+        );
+    }
+
+    #[test]
+    fn comment() {
+        let src = r#"
+        /* outer /* inner */ still comment */ a // line
+        /// first line
+        ///second line
+        //// not a doc comment
+        let b /**/ = 1;
+        /* unterminated /* */"#;
+        let results: Vec<_> = Lexer::new(src)
+            .map(|result| {
+                result
+                    .map(|t| (t.token_kind, t.value.into_owned()))
+                    .map_err(|e| e.kind)
+            })
+            .collect();
+        assert_eq!(
+            results,
+            vec![
+                Ok((TokenKind::Ident, "a".to_string())),
+                Ok((TokenKind::DocComment, "first line".to_string())),
+                Ok((TokenKind::DocComment, "second line".to_string())),
+                Ok((TokenKind::Let, "let".to_string())),
+                Ok((TokenKind::Ident, "b".to_string())),
+                Ok((TokenKind::Assign, "=".to_string())),
+                Ok((TokenKind::IntLiteral, "1".to_string())),
+                Ok((TokenKind::SemiColon, ";".to_string())),
+                Err(LexErrorKind::UnterminatedComment),
+                Ok((TokenKind::Eof, "".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn numeric_literal() {
+        let src = "0 1_000_000 0x1F 0o17 0b1010_0101 3.14 6.02e23 1E-9 2.5e+3 \
+                   1.abs 1.2.3 0x 0b102 1e 12ab 0x1.5";
+        let results: Vec<_> = Lexer::new(src)
+            .map(|result| {
+                result
+                    .map(|t| (t.token_kind, t.value.into_owned()))
+                    .map_err(|e| e.kind)
+            })
+            .collect();
+        let int = |s: &str| Ok((TokenKind::IntLiteral, s.to_string()));
+        let double = |s: &str| Ok((TokenKind::DoubleLiteral, s.to_string()));
+        let malformed = |s: &str| Err(LexErrorKind::MalformedNumber(s.to_string()));
+        assert_eq!(
+            results,
+            vec![
+                int("0"),
+                int("1_000_000"),
+                int("0x1F"),
+                int("0o17"),
+                int("0b1010_0101"),
+                double("3.14"),
+                double("6.02e23"),
+                double("1E-9"),
+                double("2.5e+3"),
+                int("1"),
+                Ok((TokenKind::Dot, ".".to_string())),
+                Ok((TokenKind::Ident, "abs".to_string())),
+                malformed("1.2.3"),
+                malformed("0x"),
+                malformed("0b102"),
+                malformed("1e"),
+                malformed("12ab"),
+                malformed("0x1.5"),
+                Ok((TokenKind::Eof, "".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn symbol() {
+        let src = "< > <= >= << >> % ** * & | ^ && || += -= *= /= = == 0..10 a..=b . ->";
+        let kinds: Vec<_> = tokenize(src)
+            .unwrap()
+            .iter()
+            .map(|t| t.token_kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::LessThan,
+                TokenKind::GreaterThan,
+                TokenKind::LessEqual,
+                TokenKind::GreaterEqual,
+                TokenKind::ShiftLeft,
+                TokenKind::ShiftRight,
+                TokenKind::Percent,
+                TokenKind::Power,
+                TokenKind::Asterisk,
+                TokenKind::BitAnd,
+                TokenKind::BitOr,
+                TokenKind::BitXor,
+                TokenKind::And,
+                TokenKind::Or,
+                TokenKind::PlusAssign,
+                TokenKind::MinusAssign,
+                TokenKind::AsteriskAssign,
+                TokenKind::SlashAssign,
+                TokenKind::Assign,
+                TokenKind::Equal,
+                TokenKind::IntLiteral,
+                TokenKind::DotDot,
+                TokenKind::IntLiteral,
+                TokenKind::Ident,
+                TokenKind::DotDotEqual,
+                TokenKind::Ident,
+                TokenKind::Dot,
+                TokenKind::Arrow,
+                TokenKind::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn lossless() {
+        let src = "/// doc\r\nlet a = 1; // line\n\t/* block /* nested */ */ r\"raw\" \"\\tx\"\n";
+        let mut text = String::new();
+        let mut kinds = Vec::new();
+        for token in Lexer::lossless(src) {
+            let token = token.unwrap();
+            text.push_str(token.text(src));
+            kinds.push(token.token_kind);
+        }
+        assert_eq!(text, src);
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::DocComment,
+                TokenKind::Whitespace,
+                TokenKind::Let,
+                TokenKind::Whitespace,
+                TokenKind::Ident,
+                TokenKind::Whitespace,
+                TokenKind::Assign,
+                TokenKind::Whitespace,
+                TokenKind::IntLiteral,
+                TokenKind::SemiColon,
+                TokenKind::Whitespace,
+                TokenKind::Comment,
+                TokenKind::Whitespace,
+                TokenKind::Comment,
+                TokenKind::Whitespace,
+                TokenKind::StringLiteral,
+                TokenKind::Whitespace,
+                TokenKind::StringLiteral,
+                TokenKind::Whitespace,
+                TokenKind::Eof,
+            ]
+        );
+    }
+
+    #[test]
+    fn borrowed_value() {
+        let src = String::from(r#"name "plain" "esc\n""#);
+        let tokens: Vec<Token<'static>> = {
+            let tokens = tokenize(&src).unwrap();
+            assert!(matches!(tokens[0].value, Cow::Borrowed("name")));
+            assert!(matches!(tokens[1].value, Cow::Borrowed("plain")));
+            assert!(matches!(tokens[2].value, Cow::Owned(_)));
+            tokens.into_iter().map(Token::into_owned).collect()
+        };
+        drop(src);
+        assert_eq!(tokens[2].value, "esc\n");
+    }
+
+    #[test]
+    fn identifier() {
+        let results: Vec<_> = Lexer::new("_tmp 変数名 café x1 foo.bar a#b x$ ٣ 'outer: ' break")
+            .map(|result| {
+                result
+                    .map(|t| (t.token_kind, t.value.into_owned()))
+                    .map_err(|e| e.kind)
+            })
+            .collect();
+        let ident = |s: &str| Ok((TokenKind::Ident, s.to_string()));
+        let invalid = |c: char| Err(LexErrorKind::InvalidCharacter(c));
+        assert_eq!(
+            results,
+            vec![
+                ident("_tmp"),
+                ident("変数名"),
+                ident("café"),
+                ident("x1"),
+                ident("foo"),
+                Ok((TokenKind::Dot, ".".to_string())),
+                ident("bar"),
+                ident("a"),
+                invalid('#'),
+                ident("b"),
+                ident("x"),
+                invalid('$'),
+                invalid('٣'),
+                Ok((TokenKind::Label, "outer".to_string())),
+                Ok((TokenKind::Colon, ":".to_string())),
+                invalid('\''),
+                Ok((TokenKind::Break, "break".to_string())),
+                Ok((TokenKind::Eof, "".to_string())),
+            ]
+        );
     }
 }
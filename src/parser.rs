@@ -1,108 +1,344 @@
-use crate::ast::expression::Expression;
+use crate::ast::expression::{Expression, Parameter};
 use crate::ast::program::Program;
 use crate::ast::statement::Statement;
-use crate::lexer::Lexer;
-use crate::token::{get_priority, Priority, Token, TokenKind};
+use crate::ast::types::Type;
+use crate::lexer::{LexError, LexErrorKind, Lexer};
+use crate::stack::ensure_sufficient_stack;
+use crate::token::{get_priority, is_right_associative, Priority, Span, Token, TokenKind};
 use core::fmt;
+use std::error;
+use std::mem;
+use std::num::IntErrorKind;
 
-pub struct Parser {
-    lexer: Lexer,
-    token: Token,
-    peek: Option<Token>,
+// 式と文の入れ子の上限。これを超える入力はスタックを使い切る前にエラーにする
+// （ブロックの中の式の中のブロック…のように混ざるので、式と文で同じ深さを数える）
+const MAX_NESTING: usize = 256;
+
+pub struct Parser<'src> {
+    lexer: Lexer<'src>,
+    token: Token<'src>,
+    peek: Token<'src>,
+    doc: Option<String>,      // token の直前にあったドキュメントコメント
+    peek_doc: Option<String>, // peek の直前にあったドキュメントコメント
+    errors: Vec<ParseError>,
+    depth: usize,               // 現在の式と文の入れ子の深さ
+    braces: isize,              // token より前にある閉じていない { の数（エラーからの復帰に使う）
+    loops: Vec<Option<String>>, // 解析中の文を囲むループのラベル（内側が後ろ）
+}
+
+// 構文解析の結果。エラーがあっても解析できた文は program に入る
+pub struct ParseResult {
+    pub program: Program,
+    pub errors: Vec<ParseError>,
 }
 
-impl Parser {
-    pub fn new(mut lexer: Lexer) -> Self {
-        let peek = lexer.next_token();
-        Parser {
+impl<'src> Parser<'src> {
+    pub fn new(lexer: Lexer<'src>) -> Self {
+        let mut parser = Parser {
             lexer,
-            token: Token::new(TokenKind::Other, "".to_string()),
-            peek,
-        }
+            token: Token::new(TokenKind::Other, "", Span::default()),
+            peek: Token::new(TokenKind::Eof, "", Span::default()),
+            doc: None,
+            peek_doc: None,
+            errors: Vec::new(),
+            depth: 0,
+            braces: 0,
+            loops: Vec::new(),
+        };
+        parser.peek = parser.read_token();
+        parser
     }
 
-    fn expect_next(&mut self, token_kind: TokenKind) -> Result<Token, ParseError> {
-        match &self.peek {
-            Some(t) if t.is_same_kind(token_kind) => {
-                self.next_token();
-                Result::Ok(self.token.clone())
-            }
-            Some(t) => {
-                let message = format!(
-                    "expected next token to be {:?}, got {:?} instead",
-                    token_kind, t.token_kind
-                );
-                ParseError::throw(message)
-            }
-            _ => {
-                let message = format!("expected next token is {:?}", token_kind);
-                ParseError::throw(message)
+    // 字句エラーは errors に記録し、次に読めるトークンまで進める
+    // ドキュメントコメントはトークンとしては返さず、peek_doc にまとめておく
+    fn read_token(&mut self) -> Token<'src> {
+        let mut docs: Vec<String> = Vec::new();
+        loop {
+            match self.lexer.next_token() {
+                Ok(token) if token.is_same_kind(TokenKind::DocComment) => {
+                    docs.push(token.value.into_owned())
+                }
+                Ok(token) => {
+                    self.peek_doc = (!docs.is_empty()).then(|| docs.join("\n"));
+                    return token;
+                }
+                Err(err) => self.errors.push(ParseError::from(err)),
             }
         }
     }
 
+    fn expect_next(&mut self, token_kind: TokenKind) -> Result<Token<'src>, ParseError> {
+        if self.peek_token_is(token_kind) {
+            self.next_token();
+            Result::Ok(self.token.clone())
+        } else {
+            Err(self.unexpected_peek(vec![token_kind]))
+        }
+    }
+
+    // peek が expected のいずれでもなかったときのエラー
+    fn unexpected_peek(&self, expected: Vec<TokenKind>) -> ParseError {
+        let kind = match self.peek.token_kind {
+            TokenKind::Eof => ParseErrorKind::UnexpectedEof { expected },
+            found => ParseErrorKind::UnexpectedToken { expected, found },
+        };
+        ParseError {
+            kind,
+            span: self.peek.span,
+        }
+    }
+
     fn peek_token_is(&self, token_kind: TokenKind) -> bool {
-        match &self.peek {
-            Some(p) => p.is_same_kind(token_kind),
-            None => false,
+        self.peek.is_same_kind(token_kind)
+    }
+
+    // 文の先頭の { (self.token) がハッシュリテラルを始めるか（Lexer を複製して先読みする）
+    // 括弧の外の : が ; や対応する } より先にあればハッシュリテラル。
+    // キーは { a[0]: 1 } や { "a" + "b": 1 } のように任意の式でよい。
+    // 空の {} と、: より先に括弧の外で文を始めるキーワードやラベルが現れるものはブロック
+    // （let x: int や 'label: while の : はキーの区切りではない）。
+    // 括弧の外の fn もブロックとみなす（fn () : int の : があるため。関数はハッシュのキーにならない）
+    fn starts_hash_literal(&self) -> bool {
+        let mut lexer = self.lexer.clone();
+        let mut kind = self.peek.token_kind;
+        let mut depth = 0usize;
+        loop {
+            match kind {
+                // 解析しても NestingTooDeep になるので、それ以上は読まない
+                TokenKind::Lparen | TokenKind::Lsquare | TokenKind::Lcurly
+                    if depth >= MAX_NESTING =>
+                {
+                    return false
+                }
+                TokenKind::Lparen | TokenKind::Lsquare | TokenKind::Lcurly => depth += 1,
+                TokenKind::Rparen | TokenKind::Rsquare | TokenKind::Rcurly if depth > 0 => {
+                    depth -= 1
+                }
+                TokenKind::Colon if depth == 0 => return true,
+                TokenKind::SemiColon
+                | TokenKind::Fn
+                | TokenKind::Let
+                | TokenKind::Int
+                | TokenKind::Double
+                | TokenKind::Boolean
+                | TokenKind::Return
+                | TokenKind::While
+                | TokenKind::For
+                | TokenKind::Break
+                | TokenKind::Continue
+                | TokenKind::Label
+                    if depth == 0 =>
+                {
+                    return false
+                }
+                TokenKind::Rparen | TokenKind::Rsquare | TokenKind::Rcurly | TokenKind::Eof => {
+                    return false
+                }
+                _ => (),
+            }
+            kind = match lexer.next_token() {
+                Ok(token) => token.token_kind,
+                Err(_) => return false,
+            };
         }
     }
 
+    // Eof に達したら false を返す（それ以上は進まない）
     fn next_token(&mut self) -> bool {
-        match &self.peek {
-            Some(p) => {
-                self.token = p.clone();
-                self.peek = self.lexer.next_token();
-                true
-            }
-            None => false,
+        if self.token.is_same_kind(TokenKind::Eof) {
+            return false;
         }
+        match self.token.token_kind {
+            TokenKind::Lcurly => self.braces += 1,
+            TokenKind::Rcurly => self.braces -= 1,
+            _ => (),
+        }
+        self.doc = self.peek_doc.take();
+        let peek = self.read_token();
+        self.token = mem::replace(&mut self.peek, peek);
+        !self.token.is_same_kind(TokenKind::Eof)
     }
 
-    pub fn parse_program(&mut self) -> Program {
-        let program = Program::new();
+    pub fn parse_program(&mut self) -> ParseResult {
+        let mut program = Program::new();
 
         while self.next_token() {
-            match self.parse_statement() {
-                Ok(stmt) => println!("{}", stmt),
-                Err(err) => err.println(),
+            let stmt = self.parse_statement_with_recovery();
+            program.statements.push(stmt);
+        }
+        ParseResult {
+            program,
+            errors: mem::take(&mut self.errors),
+        }
+    }
+
+    // 文を解析する。失敗した場合はエラーを記録し、次の文の手前まで読み飛ばして
+    // ErrorStatement を返す
+    fn parse_statement_with_recovery(&mut self) -> Statement {
+        let token = self.token.clone().into_owned();
+        let braces = self.braces;
+        match self.parse_statement() {
+            Ok(stmt) => stmt,
+            Err(err) => {
+                self.errors.push(err);
+                self.synchronize(braces);
+                Statement::ErrorStatement { token }
             }
         }
-        return program;
+    }
+
+    // 文の区切り（; や }）か、次の文の始まりの手前まで進める
+    // braces は文の始まりの { の数。文の中で開いた { ... } の中の ; や } では止まらない
+    fn synchronize(&mut self, braces: isize) {
+        loop {
+            let at_statement_level = self.braces == braces;
+            match self.token.token_kind {
+                TokenKind::SemiColon | TokenKind::Rcurly if at_statement_level => return,
+                TokenKind::Eof => return,
+                _ => (),
+            }
+            let peek_at_statement_level = match self.token.token_kind {
+                TokenKind::Lcurly => self.braces + 1 == braces,
+                TokenKind::Rcurly => self.braces - 1 == braces,
+                _ => at_statement_level,
+            };
+            if peek_at_statement_level && self.peek_token_is(TokenKind::Rcurly) {
+                return;
+            }
+            match self.peek.token_kind {
+                TokenKind::Let
+                | TokenKind::Int
+                | TokenKind::Double
+                | TokenKind::Boolean
+                | TokenKind::Return
+                | TokenKind::Fn
+                | TokenKind::While
+                | TokenKind::For
+                | TokenKind::Label
+                | TokenKind::Break
+                | TokenKind::Continue
+                | TokenKind::Eof => return,
+                _ => (),
+            }
+            self.next_token();
+        }
     }
 
     fn parse_statement(&mut self) -> Result<Statement, ParseError> {
+        if self.depth >= MAX_NESTING {
+            return ParseError::throw(ParseErrorKind::NestingTooDeep, self.token.span);
+        }
+        self.depth += 1;
+        let statement = ensure_sufficient_stack(|| self.parse_statement_inner());
+        self.depth -= 1;
+        statement
+    }
+
+    fn parse_statement_inner(&mut self) -> Result<Statement, ParseError> {
         match self.token.token_kind {
             TokenKind::Let => self.parse_let_statement(),
+            TokenKind::Int | TokenKind::Double | TokenKind::Boolean => {
+                self.parse_typed_declaration()
+            }
             TokenKind::Return => self.parse_return_statement(),
+            // fn の後に名前があれば宣言、なければ関数リテラルの式文
+            TokenKind::Fn if self.peek_token_is(TokenKind::Ident) => {
+                self.parse_function_declaration()
+            }
+            TokenKind::While => self.parse_while_statement(None),
+            TokenKind::For => self.parse_for_statement(None),
+            TokenKind::Label => self.parse_labeled_statement(),
+            TokenKind::Break | TokenKind::Continue => self.parse_loop_control_statement(),
+            // 文の先頭の { はブロック。ただし { key: ... と続くならハッシュリテラル
+            TokenKind::Lcurly if !self.starts_hash_literal() => self.parse_block_statement(),
             _ => self.parse_expression_statement(),
         }
     }
 
+    // let x = 1; または let x: int = 1;
     fn parse_let_statement(&mut self) -> Result<Statement, ParseError> {
-        let token = self.token.clone(); // let のはず
-        let ident = self.expect_next(TokenKind::Ident)?;
+        let token = self.token.clone().into_owned(); // let のはず
+        let doc = self.doc.take();
+        let ident = self.expect_next(TokenKind::Ident)?.value.into_owned();
+
+        let var_type = if self.peek_token_is(TokenKind::Colon) {
+            self.next_token();
+            self.next_token();
+            Some(self.parse_type()?)
+        } else {
+            None
+        };
+
+        self.parse_declaration(token, doc, ident, var_type)
+    }
+
+    // int x = 1; のような C 風の宣言。型を付けた let と同じものになる
+    fn parse_typed_declaration(&mut self) -> Result<Statement, ParseError> {
+        let token = self.token.clone().into_owned(); // int, double, boolean のいずれか
+        let doc = self.doc.take();
+        let var_type = self.parse_type()?;
+        let ident = self.expect_next(TokenKind::Ident)?.value.into_owned();
+        self.parse_declaration(token, doc, ident, Some(var_type))
+    }
+
+    // 変数名（と型）の後の = value; を読む。型と合わないリテラルはここでエラーにする
+    fn parse_declaration(
+        &mut self,
+        token: Token<'static>,
+        doc: Option<String>,
+        ident: String,
+        var_type: Option<Type>,
+    ) -> Result<Statement, ParseError> {
         self.expect_next(TokenKind::Assign)?;
 
         self.next_token();
+        let span = self.token.span;
         let value = self.parse_expression(Priority::Lowest as u8)?;
 
+        if let (Some(expected), Some(found)) = (var_type, value.literal_type()) {
+            if !expected.accepts(found) {
+                let kind = ParseErrorKind::TypeMismatch { expected, found };
+                return ParseError::throw(kind, span);
+            }
+        }
+
         if self.peek_token_is(TokenKind::SemiColon) {
             self.next_token();
         }
 
         Result::Ok(Statement::LetStatement {
             token,
-            identfier: Expression::Identifier(ident.value),
+            doc,
+            identfier: Expression::Identifier(ident),
+            var_type,
             value,
         })
     }
 
+    // self.token が型名のキーワード
+    fn parse_type(&mut self) -> Result<Type, ParseError> {
+        match Type::from_token_kind(self.token.token_kind) {
+            Some(t) => Ok(t),
+            None => {
+                let kind = ParseErrorKind::ExpectedType {
+                    found: self.token.token_kind,
+                };
+                ParseError::throw(kind, self.token.span)
+            }
+        }
+    }
+
     fn parse_return_statement(&mut self) -> Result<Statement, ParseError> {
-        let token = self.token.clone(); // return のはず
+        let token = self.token.clone().into_owned(); // return のはず
 
-        self.next_token();
-        let return_value = self.parse_expression(Priority::Lowest as u8)?;
+        // return; や return } は値を返さない
+        let return_value = match self.peek.token_kind {
+            TokenKind::SemiColon | TokenKind::Rcurly | TokenKind::Eof => None,
+            _ => {
+                self.next_token();
+                Some(self.parse_expression(Priority::Lowest as u8)?)
+            }
+        };
 
         if self.peek_token_is(TokenKind::SemiColon) {
             self.next_token();
@@ -115,7 +351,7 @@ impl Parser {
     }
 
     fn parse_expression_statement(&mut self) -> Result<Statement, ParseError> {
-        let token = self.token.clone();
+        let token = self.token.clone().into_owned();
 
         let expression = self.parse_expression(Priority::Lowest as u8)?;
 
@@ -126,93 +362,349 @@ impl Parser {
         Ok(Statement::ExpressionStatement { token, expression })
     }
 
+    // 'label: while ... / 'label: for ...
+    fn parse_labeled_statement(&mut self) -> Result<Statement, ParseError> {
+        let label = self.token.value.to_string();
+        self.expect_next(TokenKind::Colon)?;
+        match self.peek.token_kind {
+            TokenKind::While => {
+                self.next_token();
+                self.parse_while_statement(Some(label))
+            }
+            TokenKind::For => {
+                self.next_token();
+                self.parse_for_statement(Some(label))
+            }
+            _ => Err(self.unexpected_peek(vec![TokenKind::While, TokenKind::For])),
+        }
+    }
+
+    fn parse_while_statement(&mut self, label: Option<String>) -> Result<Statement, ParseError> {
+        let token = self.token.clone().into_owned(); // while のはず
+        self.expect_next(TokenKind::Lparen)?; // (
+
+        self.next_token();
+        let condition = self.parse_expression(Priority::Lowest as u8)?;
+
+        self.expect_next(TokenKind::Rparen)?; // )
+        self.expect_next(TokenKind::Lcurly)?; // {
+
+        let body = Box::new(self.parse_loop_body(label.clone())?);
+
+        Ok(Statement::WhileStatement {
+            token,
+            label,
+            condition,
+            body,
+        })
+    }
+
+    fn parse_for_statement(&mut self, label: Option<String>) -> Result<Statement, ParseError> {
+        let token = self.token.clone().into_owned(); // for のはず
+
+        let mut variables = vec![self.expect_next(TokenKind::Ident)?.value.into_owned()];
+        if self.peek_token_is(TokenKind::Comma) {
+            self.next_token();
+            variables.push(self.expect_next(TokenKind::Ident)?.value.into_owned());
+        }
+        self.expect_next(TokenKind::In)?;
+
+        self.next_token();
+        let iterable = self.parse_expression(Priority::Lowest as u8)?;
+
+        self.expect_next(TokenKind::Lcurly)?; // {
+        let body = Box::new(self.parse_loop_body(label.clone())?);
+
+        Ok(Statement::ForStatement {
+            token,
+            label,
+            variables,
+            iterable,
+            body,
+        })
+    }
+
+    // ループの本体のブロック。中の break / continue の対象を確認できるように label を積む
+    fn parse_loop_body(&mut self, label: Option<String>) -> Result<Statement, ParseError> {
+        self.loops.push(label);
+        let body = self.parse_block_statement();
+        self.loops.pop();
+        body
+    }
+
+    // break と continue はループの中でしか使えない（ラベルは囲むループのもののみ）
+    fn parse_loop_control_statement(&mut self) -> Result<Statement, ParseError> {
+        let token = self.token.clone().into_owned(); // break か continue のはず
+        if self.loops.is_empty() {
+            let kind = ParseErrorKind::OutsideLoop(token.token_kind);
+            return ParseError::throw(kind, token.span);
+        }
+
+        let label = if self.peek_token_is(TokenKind::Label) {
+            self.next_token();
+            let label = self.token.value.to_string();
+            if !self.loops.iter().flatten().any(|l| *l == label) {
+                let kind = ParseErrorKind::UndeclaredLabel(label);
+                return ParseError::throw(kind, self.token.span);
+            }
+            Some(label)
+        } else {
+            None
+        };
+
+        if self.peek_token_is(TokenKind::SemiColon) {
+            self.next_token();
+        }
+
+        match token.token_kind {
+            TokenKind::Break => Ok(Statement::BreakStatement { token, label }),
+            _ => Ok(Statement::ContinueStatement { token, label }),
+        }
+    }
+
     fn parse_block_statement(&mut self) -> Result<Statement, ParseError> {
-        let token = self.token.clone();
+        let token = self.token.clone().into_owned();
+        let braces = self.braces; // この { の手前
         let mut statements: Vec<Statement> = Vec::new();
         self.next_token();
         while !self.token.is_same_kind(TokenKind::Rcurly) {
-            statements.push(self.parse_statement()?);
+            if self.token.is_same_kind(TokenKind::Eof) {
+                let kind = ParseErrorKind::UnexpectedEof {
+                    expected: vec![TokenKind::Rcurly],
+                };
+                return ParseError::throw(kind, self.token.span);
+            }
+
+            let stmt = self.parse_statement_with_recovery();
+            let recovered_at_end = matches!(stmt, Statement::ErrorStatement { .. })
+                && self.token.is_same_kind(TokenKind::Rcurly)
+                && self.braces == braces + 1;
+            statements.push(stmt);
+            // エラーがこのブロックを閉じる } で止まった場合、そこでブロックは終わり
+            if recovered_at_end {
+                break;
+            }
             self.next_token();
         }
         Ok(Statement::BlockStatement { token, statements })
     }
 
     fn parse_expression(&mut self, priority: u8) -> Result<Expression, ParseError> {
+        if self.depth >= MAX_NESTING {
+            return ParseError::throw(ParseErrorKind::NestingTooDeep, self.token.span);
+        }
+        self.depth += 1;
+        let expression = ensure_sufficient_stack(|| self.parse_expression_inner(priority));
+        self.depth -= 1;
+        expression
+    }
+
+    fn parse_expression_inner(&mut self, priority: u8) -> Result<Expression, ParseError> {
         // prefix
-        let mut left = match self.token.token_kind {
-            TokenKind::Ident => self.parse_identifier()?,
-            TokenKind::IntLiteral => self.parse_integer_literal()?,
-            TokenKind::BoolLiteral => self.parse_boolean_literal()?,
-            TokenKind::Bang | TokenKind::Minus => self.parse_prefix_expression()?,
-            TokenKind::Lparen => self.parse_grouped_expression()?,
-            TokenKind::If => self.parse_if_expression()?,
-            TokenKind::Fn => self.parse_function_literal()?,
-            other => ParseError::throw(format!("no prefix but found {:?}", other))?,
+        let prefix: fn(&mut Self) -> Result<Expression, ParseError> = match self.token.token_kind {
+            TokenKind::Ident => Self::parse_identifier,
+            TokenKind::IntLiteral => Self::parse_integer_literal,
+            TokenKind::DoubleLiteral => Self::parse_double_literal,
+            TokenKind::BoolLiteral => Self::parse_boolean_literal,
+            TokenKind::StringLiteral => Self::parse_string_literal,
+            TokenKind::Lsquare => Self::parse_array_literal,
+            TokenKind::Lcurly => Self::parse_hash_literal,
+            TokenKind::Bang | TokenKind::Minus => Self::parse_prefix_expression,
+            TokenKind::Lparen => Self::parse_grouped_expression,
+            TokenKind::If => Self::parse_if_expression,
+            TokenKind::Fn => Self::parse_function_literal,
+            TokenKind::Eof => {
+                let kind = ParseErrorKind::UnexpectedEof { expected: vec![] };
+                return ParseError::throw(kind, self.token.span);
+            }
+            other => {
+                let kind = ParseErrorKind::ExpectedExpression { found: other };
+                return ParseError::throw(kind, self.token.span);
+            }
         };
+        // 呼び出しを 1 か所にまとめて、再帰するこの関数のスタックフレームを小さく保つ
+        let mut left = prefix(self)?;
 
         while !self.peek_token_is(TokenKind::SemiColon) && priority < self.peek_priority() as u8 {
-            let peek = self.peek.clone().unwrap();
             // infix
-            let right = match peek.token_kind {
+            let right = match self.peek.token_kind {
                 TokenKind::Plus
                 | TokenKind::Minus
                 | TokenKind::Slash
                 | TokenKind::Asterisk
+                | TokenKind::Percent
+                | TokenKind::Power
                 | TokenKind::Equal
                 | TokenKind::NotEqual
                 | TokenKind::LessThan
-                | TokenKind::GreaterThan => Self::parse_infix_expression,
+                | TokenKind::GreaterThan
+                | TokenKind::LessEqual
+                | TokenKind::GreaterEqual
+                | TokenKind::BitAnd
+                | TokenKind::BitOr
+                | TokenKind::BitXor
+                | TokenKind::ShiftLeft
+                | TokenKind::ShiftRight => Self::parse_infix_expression,
+                TokenKind::Assign
+                | TokenKind::PlusAssign
+                | TokenKind::MinusAssign
+                | TokenKind::AsteriskAssign
+                | TokenKind::SlashAssign => Self::parse_assign_expression,
+                TokenKind::And | TokenKind::Or => Self::parse_logical_expression,
+                TokenKind::DotDot | TokenKind::DotDotEqual => Self::parse_range_expression,
                 TokenKind::Lparen => Self::parse_call_expression,
+                TokenKind::Dot => Self::parse_member_access_expression,
+                TokenKind::Lsquare => Self::parse_index_expression,
                 _ => break,
             };
             self.next_token();
             left = right(self, left)?;
         }
-        return Ok(left);
+        Ok(left)
     }
 
     fn peek_priority(&self) -> Priority {
-        match &self.peek {
-            Some(peek) => get_priority(peek.token_kind),
-            None => Priority::Lowest,
-        }
+        get_priority(self.peek.token_kind)
     }
 
-    fn parse_identifier(&self) -> Result<Expression, ParseError> {
-        Ok(Expression::Identifier(self.token.clone().value))
+    fn parse_identifier(&mut self) -> Result<Expression, ParseError> {
+        Ok(Expression::Identifier(self.token.value.to_string()))
     }
 
-    fn parse_integer_literal(&self) -> Result<Expression, ParseError> {
-        match self.token.value.parse::<i32>() {
+    fn parse_integer_literal(&mut self) -> Result<Expression, ParseError> {
+        let text = self.token.value.replace('_', "");
+        let (radix, digits) = match text.get(..2) {
+            Some("0x") => (16, &text[2..]),
+            Some("0o") => (8, &text[2..]),
+            Some("0b") => (2, &text[2..]),
+            _ => (10, text.as_str()),
+        };
+
+        match i32::from_str_radix(digits, radix) {
             Ok(number) => Ok(Expression::IntegerLiteral(number)),
-            Err(_) => {
-                ParseError::throw(format!("could not parse {} as integer.", self.token.value))
-            }
+            Err(err) if *err.kind() == IntErrorKind::PosOverflow => ParseError::throw(
+                ParseErrorKind::IntegerOverflow(self.token.value.to_string()),
+                self.token.span,
+            ),
+            Err(_) => ParseError::throw(
+                ParseErrorKind::InvalidIntegerLiteral(self.token.value.to_string()),
+                self.token.span,
+            ),
         }
     }
 
-    fn parse_boolean_literal(&self) -> Result<Expression, ParseError> {
+    fn parse_double_literal(&mut self) -> Result<Expression, ParseError> {
+        // 1e999 のように f64 に収まらないものは inf にせずエラーにする
+        match self.token.value.replace('_', "").parse::<f64>() {
+            Ok(number) if number.is_finite() => Ok(Expression::DoubleLiteral(number)),
+            _ => ParseError::throw(
+                ParseErrorKind::InvalidDoubleLiteral(self.token.value.to_string()),
+                self.token.span,
+            ),
+        }
+    }
+
+    fn parse_boolean_literal(&mut self) -> Result<Expression, ParseError> {
         match self.token.value.parse::<bool>() {
             Ok(boolean) => Ok(Expression::BooleanLiteral(boolean)),
-            Err(_) => {
-                ParseError::throw(format!("could not parse {} as boolean.", self.token.value))
+            Err(_) => ParseError::throw(
+                ParseErrorKind::InvalidBooleanLiteral(self.token.value.to_string()),
+                self.token.span,
+            ),
+        }
+    }
+
+    fn parse_string_literal(&mut self) -> Result<Expression, ParseError> {
+        Ok(Expression::StringLiteral(self.token.value.to_string()))
+    }
+
+    fn parse_array_literal(&mut self) -> Result<Expression, ParseError> {
+        Ok(Expression::ArrayLiteral(
+            self.parse_expression_list(TokenKind::Rsquare)?,
+        ))
+    }
+
+    // 式の位置の { は常にハッシュリテラル
+    fn parse_hash_literal(&mut self) -> Result<Expression, ParseError> {
+        let mut pairs = Vec::new();
+
+        while !self.peek_token_is(TokenKind::Rcurly) {
+            self.next_token();
+            let key = self.parse_expression(Priority::Lowest as u8)?;
+            self.expect_next(TokenKind::Colon)?;
+            self.next_token();
+            let value = self.parse_expression(Priority::Lowest as u8)?;
+            pairs.push((key, value));
+
+            if !self.peek_token_is(TokenKind::Rcurly) {
+                self.expect_next(TokenKind::Comma)?;
             }
         }
+
+        self.expect_next(TokenKind::Rcurly)?; // }
+        Ok(Expression::HashLiteral(pairs))
     }
 
     fn parse_function_literal(&mut self) -> Result<Expression, ParseError> {
+        let (parameters, return_type, body) = self.parse_function_rest()?;
+        Ok(Expression::FunctionLiteral {
+            parameters,
+            return_type,
+            body,
+        })
+    }
+
+    fn parse_function_declaration(&mut self) -> Result<Statement, ParseError> {
+        let token = self.token.clone().into_owned(); // fn のはず
+        let doc = self.doc.take();
+        let name = self.expect_next(TokenKind::Ident)?.value.into_owned();
+        let (parameters, return_type, body) = self.parse_function_rest()?;
+
+        if self.peek_token_is(TokenKind::SemiColon) {
+            self.next_token();
+        }
+
+        Ok(Statement::FunctionDeclarationStatement {
+            token,
+            doc,
+            name,
+            parameters,
+            return_type,
+            body,
+        })
+    }
+
+    // 関数リテラルと関数宣言で共通の (仮引数) -> 型 { 本体 } の部分
+    #[allow(clippy::type_complexity)]
+    fn parse_function_rest(
+        &mut self,
+    ) -> Result<(Vec<Parameter>, Option<Type>, Box<Statement>), ParseError> {
         self.expect_next(TokenKind::Lparen)?; // (
 
         let parameters = self.parse_function_params()?;
 
+        // -> int でも : int でもよい
+        let return_type =
+            if self.peek_token_is(TokenKind::Arrow) || self.peek_token_is(TokenKind::Colon) {
+                self.next_token();
+                self.next_token();
+                Some(self.parse_type()?)
+            } else {
+                None
+            };
+
         self.expect_next(TokenKind::Lcurly)?; // {
 
-        let body = Box::new(self.parse_block_statement()?);
+        // 関数の中から外側のループは抜けられない
+        let loops = mem::take(&mut self.loops);
+        let body = self.parse_block_statement();
+        self.loops = loops;
+        let body = Box::new(body?);
 
-        Ok(Expression::FunctionLiteral { parameters, body })
+        Ok((parameters, return_type, body))
     }
 
-    fn parse_function_params(&mut self) -> Result<Vec<Box<Expression>>, ParseError> {
+    fn parse_function_params(&mut self) -> Result<Vec<Parameter>, ParseError> {
         let mut params = Vec::new();
 
         self.next_token();
@@ -220,21 +712,47 @@ impl Parser {
             return Ok(params);
         }
 
-        params.push(Box::new(Expression::Identifier(self.token.clone().value)));
+        params.push(self.parse_parameter()?);
 
         while self.peek_token_is(TokenKind::Comma) {
             self.next_token();
             self.next_token();
-            params.push(Box::new(Expression::Identifier(self.token.clone().value)));
+            params.push(self.parse_parameter()?);
         }
 
         self.expect_next(TokenKind::Rparen)?; // )
         Ok(params)
     }
 
+    // a, a: int, int a のいずれか
+    fn parse_parameter(&mut self) -> Result<Parameter, ParseError> {
+        match self.token.token_kind {
+            TokenKind::Ident => {
+                let name = self.token.value.to_string();
+                let param_type = if self.peek_token_is(TokenKind::Colon) {
+                    self.next_token();
+                    self.next_token();
+                    Some(self.parse_type()?)
+                } else {
+                    None
+                };
+                Ok(Parameter { name, param_type })
+            }
+            kind if Type::from_token_kind(kind).is_some() => {
+                let param_type = Some(self.parse_type()?);
+                let name = self.expect_next(TokenKind::Ident)?.value.into_owned();
+                Ok(Parameter { name, param_type })
+            }
+            found => {
+                let kind = ParseErrorKind::ExpectedParameter { found };
+                ParseError::throw(kind, self.token.span)
+            }
+        }
+    }
+
     fn parse_prefix_expression(&mut self) -> Result<Expression, ParseError> {
-        let token = self.token.clone();
-        let operator = token.value.clone();
+        let token = self.token.clone().into_owned();
+        let operator = token.value.to_string();
         self.next_token();
         let right = Box::new(self.parse_expression(Priority::Prefix as u8)?);
         Ok(Expression::PrefixExpression {
@@ -245,9 +763,13 @@ impl Parser {
     }
 
     fn parse_infix_expression(&mut self, left: Expression) -> Result<Expression, ParseError> {
-        let token = self.token.clone();
-        let operator = token.value.clone();
-        let priority = get_priority(self.token.token_kind) as u8;
+        let token = self.token.clone().into_owned();
+        let operator = token.value.to_string();
+        let mut priority = get_priority(self.token.token_kind) as u8;
+        // 右結合の場合は同じ優先度の演算子を右側に取り込む
+        if is_right_associative(self.token.token_kind) {
+            priority -= 1;
+        }
         self.next_token();
 
         Ok(Expression::InfixExpression {
@@ -258,15 +780,63 @@ impl Parser {
         })
     }
 
-    fn parse_grouped_expression(&mut self) -> Result<Expression, ParseError> {
+    // 代入は右結合（a = b = c は a = (b = c)）
+    fn parse_assign_expression(&mut self, target: Expression) -> Result<Expression, ParseError> {
+        let token = self.token.clone().into_owned();
+        if !matches!(
+            target,
+            Expression::Identifier(_)
+                | Expression::IndexExpression { .. }
+                | Expression::MemberAccessExpression { .. }
+        ) {
+            let kind = ParseErrorKind::InvalidAssignmentTarget(target.to_string());
+            return ParseError::throw(kind, token.span);
+        }
+        let operator = token.value.to_string();
         self.next_token();
+        let value = self.parse_expression(Priority::Assign as u8 - 1)?;
 
-        let expression = self.parse_expression(Priority::Lowest as u8)?;
+        Ok(Expression::AssignExpression {
+            token,
+            target: Box::new(target),
+            operator,
+            value: Box::new(value),
+        })
+    }
+
+    fn parse_logical_expression(&mut self, left: Expression) -> Result<Expression, ParseError> {
+        let token = self.token.clone().into_owned();
+        let operator = token.value.to_string();
+        let priority = get_priority(self.token.token_kind) as u8;
         self.next_token();
 
-        if self.peek_token_is(TokenKind::Rparen) {
-            ParseError::throw("expected ')' but not found.".to_string())?
-        }
+        Ok(Expression::LogicalExpression {
+            token,
+            left: Box::new(left),
+            operator,
+            right: Box::new(self.parse_expression(priority)?),
+        })
+    }
+
+    fn parse_range_expression(&mut self, start: Expression) -> Result<Expression, ParseError> {
+        let token = self.token.clone().into_owned();
+        let inclusive = token.is_same_kind(TokenKind::DotDotEqual);
+        self.next_token();
+        let end = self.parse_expression(Priority::Range as u8)?;
+
+        Ok(Expression::RangeExpression {
+            token,
+            start: Box::new(start),
+            end: Box::new(end),
+            inclusive,
+        })
+    }
+
+    fn parse_grouped_expression(&mut self) -> Result<Expression, ParseError> {
+        self.next_token();
+
+        let expression = self.parse_expression(Priority::Lowest as u8)?;
+        self.expect_next(TokenKind::Rparen)?; // )
 
         Ok(expression)
     }
@@ -300,38 +870,258 @@ impl Parser {
     fn parse_call_expression(&mut self, function: Expression) -> Result<Expression, ParseError> {
         Ok(Expression::CallExpression {
             function: Box::new(function),
-            arguments: self.parse_call_arguments()?,
+            arguments: self.parse_expression_list(TokenKind::Rparen)?,
         })
     }
 
-    fn parse_call_arguments(&mut self) -> Result<Vec<Box<Expression>>, ParseError> {
-        let mut arguments = Vec::new();
+    // a.b の形。a.b(c) はこれを function とする CallExpression になる
+    fn parse_member_access_expression(
+        &mut self,
+        object: Expression,
+    ) -> Result<Expression, ParseError> {
+        let member = self.expect_next(TokenKind::Ident)?;
+        Ok(Expression::MemberAccessExpression {
+            object: Box::new(object),
+            member: member.value.into_owned(),
+        })
+    }
 
+    // a[i] または a[start:end]（start と end は省略できる）
+    fn parse_index_expression(&mut self, left: Expression) -> Result<Expression, ParseError> {
         self.next_token();
-        if self.token.is_same_kind(TokenKind::Rparen) {
-            return Ok(arguments);
+        let start = if self.token.is_same_kind(TokenKind::Colon) {
+            None
+        } else {
+            let index = self.parse_expression(Priority::Lowest as u8)?;
+            if !self.peek_token_is(TokenKind::Colon) {
+                self.expect_next(TokenKind::Rsquare)?; // ]
+                return Ok(Expression::IndexExpression {
+                    left: Box::new(left),
+                    index: Box::new(index),
+                });
+            }
+            self.next_token();
+            Some(Box::new(index))
+        };
+
+        // self.token は :
+        let end = if self.peek_token_is(TokenKind::Rsquare) {
+            None
+        } else {
+            self.next_token();
+            Some(Box::new(self.parse_expression(Priority::Lowest as u8)?))
+        };
+        self.expect_next(TokenKind::Rsquare)?; // ]
+        Ok(Expression::SliceExpression {
+            left: Box::new(left),
+            start,
+            end,
+        })
+    }
+
+    // 呼び出しの引数や配列の要素。self.token は ( や [ で、end まで読む
+    fn parse_expression_list(&mut self, end: TokenKind) -> Result<Vec<Expression>, ParseError> {
+        let mut list = Vec::new();
+
+        self.next_token();
+        if self.token.is_same_kind(end) {
+            return Ok(list);
         }
 
-        arguments.push(Box::new(self.parse_expression(Priority::Lowest as u8)?));
+        list.push(self.parse_expression(Priority::Lowest as u8)?);
 
         while self.peek_token_is(TokenKind::Comma) {
             self.next_token();
             self.next_token();
-            arguments.push(Box::new(self.parse_expression(Priority::Lowest as u8)?));
+            list.push(self.parse_expression(Priority::Lowest as u8)?);
         }
 
-        self.expect_next(TokenKind::Rparen)?; // )
-        Ok(arguments)
+        self.expect_next(end)?; // ) or ]
+        Ok(list)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseErrorKind {
+    UnexpectedToken {
+        expected: Vec<TokenKind>,
+        found: TokenKind,
+    },
+    UnexpectedEof {
+        expected: Vec<TokenKind>, // 空なら式を期待していた
+    },
+    ExpectedExpression {
+        found: TokenKind,
+    },
+    InvalidIntegerLiteral(String),
+    IntegerOverflow(String),
+    InvalidBooleanLiteral(String),
+    NestingTooDeep,
+    InvalidDoubleLiteral(String),
+    OutsideLoop(TokenKind), // ループの外の break / continue
+    UndeclaredLabel(String),
+    InvalidAssignmentTarget(String),
+    ExpectedType {
+        found: TokenKind,
+    },
+    TypeMismatch {
+        expected: Type,
+        found: Type, // リテラルの型
+    },
+    ExpectedParameter {
+        found: TokenKind,
+    },
+    Lex(LexErrorKind), // 字句解析のエラー
+}
+
+impl ParseErrorKind {
+    // エラーの種類ごとの固定のコード。字句エラーは LexErrorKind のコードを使う
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParseErrorKind::UnexpectedToken { .. } => "P0001",
+            ParseErrorKind::UnexpectedEof { .. } => "P0002",
+            ParseErrorKind::ExpectedExpression { .. } => "P0003",
+            ParseErrorKind::InvalidIntegerLiteral(_) => "P0004",
+            ParseErrorKind::IntegerOverflow(_) => "P0005",
+            ParseErrorKind::InvalidBooleanLiteral(_) => "P0006",
+            ParseErrorKind::NestingTooDeep => "P0007",
+            ParseErrorKind::InvalidDoubleLiteral(_) => "P0008",
+            ParseErrorKind::OutsideLoop(_) => "P0009",
+            ParseErrorKind::UndeclaredLabel(_) => "P0010",
+            ParseErrorKind::InvalidAssignmentTarget(_) => "P0011",
+            ParseErrorKind::ExpectedType { .. } => "P0012",
+            ParseErrorKind::TypeMismatch { .. } => "P0013",
+            ParseErrorKind::ExpectedParameter { .. } => "P0014",
+            ParseErrorKind::Lex(kind) => kind.code(),
+        }
+    }
+}
+
+fn join_kinds(kinds: &[TokenKind]) -> String {
+    kinds
+        .iter()
+        .map(|k| format!("{:?}", k))
+        .collect::<Vec<String>>()
+        .join(" or ")
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseErrorKind::UnexpectedToken { expected, found } => write!(
+                f,
+                "expected next token to be {}, got {:?} instead",
+                join_kinds(expected),
+                found
+            ),
+            ParseErrorKind::UnexpectedEof { expected } if expected.is_empty() => {
+                write!(f, "expected expression but reached end of input")
+            }
+            ParseErrorKind::UnexpectedEof { expected } => write!(
+                f,
+                "expected {} but reached end of input",
+                join_kinds(expected)
+            ),
+            ParseErrorKind::ExpectedExpression { found } => {
+                write!(f, "expected expression but found {:?}", found)
+            }
+            ParseErrorKind::InvalidIntegerLiteral(s) => {
+                write!(f, "could not parse {} as integer", s)
+            }
+            ParseErrorKind::IntegerOverflow(s) => {
+                write!(f, "integer literal {} is out of range for int", s)
+            }
+            ParseErrorKind::InvalidBooleanLiteral(s) => {
+                write!(f, "could not parse {} as boolean", s)
+            }
+            ParseErrorKind::NestingTooDeep => write!(f, "nested too deeply"),
+            ParseErrorKind::InvalidDoubleLiteral(s) => {
+                write!(f, "could not parse {} as double", s)
+            }
+            ParseErrorKind::OutsideLoop(kind) => write!(f, "{:?} outside of a loop", kind),
+            ParseErrorKind::UndeclaredLabel(label) => {
+                write!(f, "use of undeclared label '{}", label)
+            }
+            ParseErrorKind::InvalidAssignmentTarget(target) => {
+                write!(f, "invalid assignment target: {}", target)
+            }
+            ParseErrorKind::ExpectedType { found } => {
+                write!(f, "expected type but found {:?}", found)
+            }
+            ParseErrorKind::TypeMismatch { expected, found } => {
+                write!(
+                    f,
+                    "mismatched types: expected {}, found {}",
+                    expected, found
+                )
+            }
+            ParseErrorKind::ExpectedParameter { found } => {
+                write!(f, "expected parameter name but found {:?}", found)
+            }
+            ParseErrorKind::Lex(kind) => write!(f, "{}", kind),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub span: Span,
+}
+
+impl ParseError {
+    fn throw<T>(kind: ParseErrorKind, span: Span) -> Result<T, Self> {
+        Err(ParseError { kind, span })
+    }
+
+    pub fn code(&self) -> &'static str {
+        self.kind.code()
+    }
+}
+
+impl From<LexError> for ParseError {
+    fn from(err: LexError) -> Self {
+        ParseError {
+            kind: ParseErrorKind::Lex(err.kind),
+            span: err.span,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "ParseError[{}] at {}: {}",
+            self.code(),
+            self.span,
+            self.kind
+        )
     }
 }
 
+impl error::Error for ParseError {}
+
+#[cfg(test)]
 mod tests {
+    use crate::ast::types::Type;
+    use crate::lexer::LexErrorKind;
     pub use crate::lexer::Lexer;
     pub use crate::parser::Parser;
+    use crate::parser::{ParseError, ParseErrorKind};
+    use crate::token::{Span, TokenKind};
+
+    // 先頭の文を 1 つ解析して、表示した文字列を返す
+    fn parse_stmt(src: &str) -> Result<String, ParseError> {
+        let mut pa = Parser::new(Lexer::new(src));
+        pa.next_token();
+        pa.parse_statement().map(|stmt| stmt.to_string())
+    }
 
     #[test]
     fn next_token() {
-        let src = r#"
+        let srcs = [
+            r#"
         let a = 10;
         let b;
         if (true) {
@@ -342,55 +1132,686 @@ mod tests {
         } else {
             let b = 100;
         }
-        "#;
-        let src = r#"
+        "#,
+            r#"
         let add = fn (a,b) {
             return a + b;
         }
-        "#;
-        let src = r#"
+        "#,
+            r#"
         let add = fn () {
             return a + b;
         }
-        "#;
-        let src = r#"
+        "#,
+            r#"
         add(a, b);
+        "#,
+        ];
+        for src in srcs {
+            let mut pa = Parser::new(Lexer::new(src));
+            let result = pa.parse_program();
+            println!("{}", result.program);
+            for err in &result.errors {
+                println!("{}", err);
+            }
+        }
+    }
+
+    #[test]
+    fn parse_program() {
+        let src = "let a = 1;\nlet b;\nadd(a, 2);";
+        let result = Parser::new(Lexer::new(src)).parse_program();
+        assert_eq!(result.program.statements.len(), 3);
+        assert_eq!(
+            result.program.to_string(),
+            "let a = 1;\n<error>\nadd(a, 2)\n"
+        );
+        assert_eq!(
+            result
+                .errors
+                .iter()
+                .map(|e| e.to_string())
+                .collect::<Vec<_>>(),
+            vec![
+                "ParseError[P0001] at 2:6: expected next token to be Assign, got SemiColon instead"
+            ]
+        );
+    }
+
+    #[test]
+    fn error_span() {
+        let err = parse_stmt("let a\n  1;").unwrap_err();
+        assert_eq!(err.span, Span::new(8, 9, 2, 3));
+        assert_eq!(
+            err.to_string(),
+            "ParseError[P0001] at 2:3: expected next token to be Assign, got IntLiteral instead"
+        );
+    }
+
+    #[test]
+    fn doc_comment() {
+        let src = r#"
+        /// adds two numbers
+        /// returns the sum
+        let add = fn (a, b) { a + b };
+        let c = 1;
         "#;
         let mut pa = Parser::new(Lexer::new(src));
-        pa.parse_program();
+        pa.next_token();
+        let add = pa.parse_statement().ok().unwrap();
+        pa.next_token();
+        let c = pa.parse_statement().ok().unwrap();
+        assert_eq!(
+            add.to_string(),
+            "/// adds two numbers\n/// returns the sum\nlet add = fn (a, b) {\n(a + b)\n};"
+        );
+        assert_eq!(c.to_string(), "let c = 1;");
     }
-}
 
-struct ParseError {
-    message: String,
-    uncheck: bool,
-}
+    #[test]
+    fn function_declaration() {
+        let tests = [
+            ("fn add(a, b) { a + b }", "fn add(a, b) {\n(a + b)\n}"),
+            (
+                "fn is_even(int n) -> boolean { n == 0 || is_odd(n - 1) };",
+                "fn is_even(n: int) -> boolean {\n((n == 0) || is_odd((n - 1)))\n}",
+            ),
+            ("fn f(): void { }", "fn f() -> void {\n\n}"),
+            ("fn f() { return; }", "fn f() {\nreturn;\n}"),
+            ("fn f() { return }", "fn f() {\nreturn;\n}"),
+            ("fn f(x) { return x }", "fn f(x) {\nreturn x;\n}"),
+            (
+                "/// does nothing\nfn noop() { }",
+                "/// does nothing\nfn noop() {\n\n}",
+            ),
+            ("fn (a) { a }(1)", "fn (a) {\na\n}(1)"),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(parse_stmt(input).unwrap(), expected, "{}", input);
+        }
+    }
 
-impl ParseError {
-    fn throw<T>(message: String) -> Result<T, Self> {
-        Err(ParseError {
-            message,
-            uncheck: false,
-        })
+    #[test]
+    fn integer_literal() {
+        let tests = [
+            ("1_000", "1000"),
+            ("0xff", "255"),
+            ("0o17", "15"),
+            ("0b1010", "10"),
+            ("2147483647", "2147483647"),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(parse_stmt(input).unwrap(), expected, "{}", input);
+        }
+
+        let err = parse_stmt("\n  0x1_0000_0000").unwrap_err();
+        assert_eq!(err.span, Span::new(3, 16, 2, 3));
+        assert_eq!(
+            err.to_string(),
+            "ParseError[P0005] at 2:3: integer literal 0x1_0000_0000 is out of range for int"
+        );
     }
 
-    fn uncheck<T>() -> Result<T, Self> {
-        Result::Err(ParseError {
-            message: "".to_string(),
-            uncheck: true,
-        })
+    #[test]
+    fn double_literal() {
+        let tests = [
+            ("3.14", Ok("3.14")),
+            ("1.0", Ok("1.0")),
+            ("1_000.5", Ok("1000.5")),
+            ("6.02e23", Ok("6.02e23")),
+            ("1E-9", Ok("1e-9")),
+            ("2.5e+3", Ok("2500.0")),
+            ("-0.5 * 2", Ok("((-0.5) * 2)")),
+            (
+                "1e999",
+                Err(ParseErrorKind::InvalidDoubleLiteral("1e999".into())),
+            ),
+        ];
+        for (input, expected) in tests {
+            let result = parse_stmt(input).map_err(|err| err.kind);
+            assert_eq!(result, expected.map(str::to_string), "{}", input);
+        }
     }
 
-    fn println(&self) {
-        if !self.uncheck {
-            println!("{}", self);
+    #[test]
+    fn operator_precedence() {
+        let tests = [
+            ("a + b * c % d", "(a + ((b * c) % d))"),
+            ("a < b == c >= d", "((a < b) == (c >= d))"),
+            ("a | b ^ c & d", "(a | (b ^ (c & d)))"),
+            ("a & b == c", "((a & b) == c)"),
+            ("1 << 2 + 3 >> 4", "((1 << (2 + 3)) >> 4)"),
+            ("a ** b ** c", "(a ** (b ** c))"),
+            ("-a ** 2", "(-(a ** 2))"),
+            ("a * b ** c", "(a * (b ** c))"),
+            ("a += b -= c * 2", "(a += (b -= (c * 2)))"),
+            ("a - b - c", "((a - b) - c)"),
+            ("a || b && c", "(a || (b && c))"),
+            ("a && b || c && d", "((a && b) || (c && d))"),
+            ("a == b && c < d", "((a == b) && (c < d))"),
+            ("!a && b | c", "((!a) && (b | c))"),
+            ("x += a || b", "(x += (a || b))"),
+        ];
+        for (src, expected) in tests {
+            assert_eq!(parse_stmt(src).unwrap(), expected, "{}", src);
         }
     }
-}
 
-impl fmt::Display for ParseError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "ParseError: {}", self.message)?;
-        Ok(())
+    #[test]
+    fn string_literal() {
+        let tests = [
+            (r#""hello""#, r#""hello""#),
+            (r#""a\"b\\c\n""#, r#""a\"b\\c\n""#),
+            (r#"r"C:\dir""#, r#""C:\\dir""#),
+            (r#"s + "!" == "hi!""#, r#"((s + "!") == "hi!")"#),
+            (r#""abc"[1]"#, r#"("abc"[1])"#),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(parse_stmt(input).unwrap(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn array_and_index() {
+        let tests = [
+            ("[]", "[]"),
+            ("[1, 2 * 3, [true]]", "[1, (2 * 3), [true]]"),
+            ("a[i + 1][j]", "((a[(i + 1)])[j])"),
+            ("f(x)[0] * 2", "((f(x)[0]) * 2)"),
+            ("-a[0]", "(-(a[0]))"),
+            ("[1, 2][-1]", "([1, 2][(-1)])"),
+            ("a[1:3]", "(a[1:3])"),
+            ("a[:n - 1]", "(a[:(n - 1)])"),
+            ("a[1:]", "(a[1:])"),
+            ("a[:]", "(a[:])"),
+            ("a.b[1:2][0]", "((a.b[1:2])[0])"),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(parse_stmt(input).unwrap(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn hash_literal() {
+        let tests = [
+            (
+                r#"{"name": "moca", 1: true}"#,
+                r#"{"name": "moca", 1: true}"#,
+            ),
+            ("{}", "{\n\n}"),
+            ("let h = {};", "let h = {};"),
+            ("{ x: 1 + 2, y: [1] }", "{x: (1 + 2), y: [1]}"),
+            ("{ x }", "{\nx\n}"),
+            ("{ let x = 1; x }", "{\nlet x = 1;x\n}"),
+            (r#"{ "a" + "b": 1 }"#, r#"{("a" + "b"): 1}"#),
+            ("{ a[0]: 1, f(x): 2 }", "{(a[0]): 1, f(x): 2}"),
+            ("{ (a): 1 }", "{a: 1}"),
+            ("{ a[1:2] }", "{\n(a[1:2])\n}"),
+            (
+                "{ f = fn () : int { 1 } }",
+                "{\n(f = fn () -> int {\n1\n})\n}",
+            ),
+            ("{ { a: 1 } }", "{\n{a: 1}\n}"),
+            (
+                "{ if (true) { 1 } let y: int = 2; y }",
+                "{\nif (true) \n\t{\n1\n} let y: int = 2;y\n}",
+            ),
+            (
+                "{ f(1) 'a: while (true) { break 'a; } }",
+                "{\nf(1)'a: while (true) {\nbreak 'a;\n}\n}",
+            ),
+            (r#"f({"a": 1}["a"])"#, r#"f(({"a": 1}["a"]))"#),
+            (r#"let h = {"a": {"b": 2}};"#, r#"let h = {"a": {"b": 2}};"#),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(parse_stmt(input).unwrap(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn while_statement() {
+        let tests = [
+            (
+                "while (i < 10) { i += 1; }",
+                "while ((i < 10)) {\n(i += 1)\n}",
+            ),
+            (
+                "while (true) { if (x) { break; } continue; }",
+                "while (true) {\nif (x) \n\t{\nbreak;\n} continue;\n}",
+            ),
+            (
+                "'outer: while (a) { while (b) { break 'outer; } }",
+                "'outer: while (a) {\nwhile (b) {\nbreak 'outer;\n}\n}",
+            ),
+            (
+                "'outer: while (a) { 'inner: while (b) { continue 'outer; } }",
+                "'outer: while (a) {\n'inner: while (b) {\ncontinue 'outer;\n}\n}",
+            ),
+            (
+                "{ 'outer: while (true) { break 'outer; } }",
+                "{\n'outer: while (true) {\nbreak 'outer;\n}\n}",
+            ),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(parse_stmt(input).unwrap(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn for_statement() {
+        let tests = [
+            ("for i in 0..10 { }", "for i in (0..10) {\n\n}"),
+            ("for i in 1..=n - 1 { }", "for i in (1..=(n - 1)) {\n\n}"),
+            ("for x in list { x; }", "for x in list {\nx\n}"),
+            ("for k, v in map { }", "for k, v in map {\n\n}"),
+            ("for x in [1, 2] { }", "for x in [1, 2] {\n\n}"),
+            (
+                "'outer: for i in a..b { for j in 0..i { break 'outer; } }",
+                "'outer: for i in (a..b) {\nfor j in (0..i) {\nbreak 'outer;\n}\n}",
+            ),
+            (
+                "{ 'outer: for x in xs { continue 'outer; } }",
+                "{\n'outer: for x in xs {\ncontinue 'outer;\n}\n}",
+            ),
+            ("0..n == 1 || x", "(0..((n == 1) || x))"),
+            ("a..b..c", "((a..b)..c)"),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(parse_stmt(input).unwrap(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn labeled_statement_error() {
+        let result = Parser::new(Lexer::new("'a: x")).parse_program();
+        assert_eq!(
+            result.errors[0].to_string(),
+            "ParseError[P0001] at 1:5: expected next token to be While or For, got Ident instead"
+        );
+    }
+
+    #[test]
+    fn loop_control_outside_loop() {
+        let tests = [
+            ("break;", ParseErrorKind::OutsideLoop(TokenKind::Break)),
+            (
+                "continue;",
+                ParseErrorKind::OutsideLoop(TokenKind::Continue),
+            ),
+            (
+                "while (a) { let f = fn () { break; }; }",
+                ParseErrorKind::OutsideLoop(TokenKind::Break),
+            ),
+            (
+                "while (a) { break 'outer; }",
+                ParseErrorKind::UndeclaredLabel("outer".to_string()),
+            ),
+            (
+                "'a: while (x) { } while (y) { continue 'a; }",
+                ParseErrorKind::UndeclaredLabel("a".to_string()),
+            ),
+        ];
+        for (input, expected) in tests {
+            let result = Parser::new(Lexer::new(input)).parse_program();
+            assert_eq!(
+                result.errors.iter().map(|e| &e.kind).collect::<Vec<_>>(),
+                vec![&expected],
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn assign_expression() {
+        let tests = [
+            ("x = x + 1;", "(x = (x + 1))"),
+            ("a = b = c", "(a = (b = c))"),
+            ("a[i] = v", "((a[i]) = v)"),
+            ("a.b = 1", "(a.b = 1)"),
+            ("a[0] += b || c", "((a[0]) += (b || c))"),
+            ("x = 0..3", "(x = (0..3))"),
+            ("x = fn (a) { a }", "(x = fn (a) {\na\n})"),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(parse_stmt(input).unwrap(), expected, "{}", input);
+        }
+
+        let tests = [
+            ("1 = 2;", "1", 3),
+            ("a + b = c;", "(a + b)", 7),
+            ("f() += 1;", "f()", 5),
+            ("a[1:2] = b;", "(a[1:2])", 8),
+        ];
+        for (input, target, column) in tests {
+            let result = Parser::new(Lexer::new(input)).parse_program();
+            let err = &result.errors[0];
+            assert_eq!(
+                err.kind,
+                ParseErrorKind::InvalidAssignmentTarget(target.to_string()),
+                "{}",
+                input
+            );
+            assert_eq!(err.span.column, column, "{}", input);
+        }
+        assert_eq!(
+            Parser::new(Lexer::new("1 = 2;")).parse_program().errors[0].to_string(),
+            "ParseError[P0011] at 1:3: invalid assignment target: 1"
+        );
+    }
+
+    #[test]
+    fn typed_declaration() {
+        let tests = [
+            ("int abc = 1234;", "let abc: int = 1234;"),
+            ("boolean b = true;", "let b: boolean = true;"),
+            ("double d = 1;", "let d: double = 1;"),
+            ("let x: int = -1;", "let x: int = (-1);"),
+            ("let d: double = 2.5 * x;", "let d: double = (2.5 * x);"),
+            ("int n = f(x);", "let n: int = f(x);"),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(parse_stmt(input).unwrap(), expected, "{}", input);
+        }
+
+        let mismatch = |expected, found| ParseErrorKind::TypeMismatch { expected, found };
+        let tests = [
+            ("int x = 1.5;", mismatch(Type::Int, Type::Double), 9),
+            (
+                "let b: boolean = 0;",
+                mismatch(Type::Boolean, Type::Int),
+                18,
+            ),
+            (
+                r#"double s = "s";"#,
+                mismatch(Type::Double, Type::String),
+                12,
+            ),
+            ("int x = -2.0;", mismatch(Type::Int, Type::Double), 9),
+            (
+                "let v: void = true;",
+                mismatch(Type::Void, Type::Boolean),
+                15,
+            ),
+            (
+                "let x: y = 1;",
+                ParseErrorKind::ExpectedType {
+                    found: TokenKind::Ident,
+                },
+                8,
+            ),
+        ];
+        for (input, expected, column) in tests {
+            let result = Parser::new(Lexer::new(input)).parse_program();
+            assert_eq!(result.errors[0].kind, expected, "{}", input);
+            assert_eq!(result.errors[0].span.column, column, "{}", input);
+        }
+        assert_eq!(
+            Parser::new(Lexer::new("int x = true;"))
+                .parse_program()
+                .errors[0]
+                .to_string(),
+            "ParseError[P0013] at 1:9: mismatched types: expected int, found boolean"
+        );
+    }
+
+    #[test]
+    fn function_signature() {
+        let tests = [
+            ("fn (a, b) { a }", "fn (a, b) {\na\n}"),
+            (
+                "fn (int a, double b) -> boolean { a < b }",
+                "fn (a: int, b: double) -> boolean {\n(a < b)\n}",
+            ),
+            ("fn (a: int): void { }", "fn (a: int) -> void {\n\n}"),
+            (
+                "fn (a, boolean b, c: int) { }",
+                "fn (a, b: boolean, c: int) {\n\n}",
+            ),
+            ("fn () -> int { 1 }", "fn () -> int {\n1\n}"),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(parse_stmt(input).unwrap(), expected, "{}", input);
+        }
+
+        let tests = [
+            (
+                "fn (1, 2) { }",
+                ParseErrorKind::ExpectedParameter {
+                    found: TokenKind::IntLiteral,
+                },
+                5,
+            ),
+            (
+                "fn (a, (b)) { }",
+                ParseErrorKind::ExpectedParameter {
+                    found: TokenKind::Lparen,
+                },
+                8,
+            ),
+            (
+                "fn (int 1) { }",
+                ParseErrorKind::UnexpectedToken {
+                    expected: vec![TokenKind::Ident],
+                    found: TokenKind::IntLiteral,
+                },
+                9,
+            ),
+            (
+                "fn (a: b) { }",
+                ParseErrorKind::ExpectedType {
+                    found: TokenKind::Ident,
+                },
+                8,
+            ),
+            (
+                "fn () -> { }",
+                ParseErrorKind::ExpectedType {
+                    found: TokenKind::Lcurly,
+                },
+                10,
+            ),
+        ];
+        for (input, expected, column) in tests {
+            let result = Parser::new(Lexer::new(input)).parse_program();
+            assert_eq!(result.errors[0].kind, expected, "{}", input);
+            assert_eq!(result.errors[0].span.column, column, "{}", input);
+        }
+    }
+
+    #[test]
+    fn member_access() {
+        let tests = [
+            ("obj.field", "obj.field"),
+            ("list.push(3)", "list.push(3)"),
+            ("s.trim().len() + 1", "(s.trim().len() + 1)"),
+            ("-a.b", "(-a.b)"),
+            ("x.y * 2", "(x.y * 2)"),
+        ];
+        for (src, expected) in tests {
+            assert_eq!(parse_stmt(src).unwrap(), expected, "{}", src);
+        }
+
+        let err = parse_stmt("a.1").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "ParseError[P0001] at 1:3: expected next token to be Ident, got IntLiteral instead"
+        );
+    }
+
+    #[test]
+    fn error_recovery() {
+        let src = r#"
+        let a = ;
+        let f = fn (x) { x + ; return x; };
+        let g = fn () { 1 + }
+        let b = 2;
+        "#;
+        let result = Parser::new(Lexer::new(src)).parse_program();
+        assert_eq!(
+            result.program.to_string(),
+            "<error>\nlet f = fn (x) {\n<error>return x;\n};\nlet g = fn () {\n<error>\n};\nlet b = 2;\n"
+        );
+        let expected = |found| ParseErrorKind::ExpectedExpression { found };
+        assert_eq!(
+            result
+                .errors
+                .iter()
+                .map(|e| (e.kind.clone(), e.span.line, e.span.column))
+                .collect::<Vec<_>>(),
+            vec![
+                (expected(TokenKind::SemiColon), 2, 17),
+                (expected(TokenKind::SemiColon), 3, 30),
+                (expected(TokenKind::Rcurly), 4, 29),
+            ]
+        );
+
+        // 関数宣言やラベル付きのループの手前で読み飛ばしをやめる
+        let src = "let x = 1 + * 2 fn f() { 1 } f()\nlet y = ) 'a: while (true) { break 'a; }";
+        let result = Parser::new(Lexer::new(src)).parse_program();
+        assert_eq!(
+            result.program.to_string(),
+            "<error>\nfn f() {\n1\n}\nf()\n<error>\n'a: while (true) {\nbreak 'a;\n}\n"
+        );
+        assert_eq!(
+            result
+                .errors
+                .iter()
+                .map(|e| (e.kind.clone(), e.span.line, e.span.column))
+                .collect::<Vec<_>>(),
+            vec![
+                (expected(TokenKind::Asterisk), 1, 13),
+                (expected(TokenKind::Rparen), 2, 9),
+            ]
+        );
+
+        // 式の中の { ... } の } はブロックの終わりではない
+        let src = "fn f() { let h = {1: }; let y = 2; y } f()";
+        let result = Parser::new(Lexer::new(src)).parse_program();
+        assert_eq!(
+            result.program.to_string(),
+            "fn f() {\n<error>let y = 2;y\n}\nf()\n"
+        );
+        assert_eq!(
+            result
+                .errors
+                .iter()
+                .map(|e| (e.kind.clone(), e.span.line, e.span.column))
+                .collect::<Vec<_>>(),
+            vec![(expected(TokenKind::Rcurly), 1, 22)]
+        );
+    }
+
+    #[test]
+    fn terminates_on_unclosed_input() {
+        let srcs = [
+            "fn() { 1",
+            "if (true) { let a = 1;",
+            "add(1, ",
+            "(((1",
+            "let",
+            "}}}",
+        ];
+        for src in srcs {
+            let result = Parser::new(Lexer::new(src)).parse_program();
+            assert!(!result.errors.is_empty(), "{}", src);
+        }
+
+        let deep = "(".repeat(10_000) + "1";
+        let result = Parser::new(Lexer::new(&deep)).parse_program();
+        assert_eq!(result.errors[0].kind, ParseErrorKind::NestingTooDeep);
+        // 式文そのものが 1 段目になる
+        assert_eq!(result.errors[0].span.column, 256);
+    }
+
+    // ブロックやループの本体も式と同じ深さで打ち切る
+    #[test]
+    fn terminates_on_deeply_nested_statements() {
+        let srcs = [
+            "{".repeat(10_000),
+            "while (x) {".repeat(10_000),
+            "for x in a {".repeat(10_000),
+            "fn f() {".repeat(10_000),
+            "if (x) { (".repeat(10_000),
+        ];
+        for src in &srcs {
+            let result = Parser::new(Lexer::new(src)).parse_program();
+            assert_eq!(
+                result.errors[0].kind,
+                ParseErrorKind::NestingTooDeep,
+                "{}",
+                &src[..20]
+            );
+        }
+
+        let ok = "{".repeat(100) + &"}".repeat(100);
+        assert!(Parser::new(Lexer::new(&ok))
+            .parse_program()
+            .errors
+            .is_empty());
+    }
+
+    // 適当なトークン列を大量に与えても必ず終了すること
+    #[test]
+    fn terminates_on_random_input() {
+        let fragments = [
+            "let", "x", "=", "1", "+", "-", "*", "(", ")", "{", "}", "[", "]", "fn", "if", "else",
+            "return", "while", ";", ",", ".", "\"s\"", "\"", "@", "/*", "true", "\n",
+        ];
+        let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+        for _ in 0..2000 {
+            let mut src = String::new();
+            for _ in 0..(seed % 40) {
+                // xorshift
+                seed ^= seed << 13;
+                seed ^= seed >> 7;
+                seed ^= seed << 17;
+                src.push_str(fragments[(seed % fragments.len() as u64) as usize]);
+                src.push(' ');
+            }
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            Parser::new(Lexer::new(&src)).parse_program();
+        }
+    }
+
+    #[test]
+    fn error_kind() {
+        let src = "let x = @;\nlet z = (a b);\nlet y = (1";
+        let result = Parser::new(Lexer::new(src)).parse_program();
+        let errors: Vec<_> = result
+            .errors
+            .iter()
+            .map(|e| (e.code(), e.kind.clone()))
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                (
+                    "L0002",
+                    ParseErrorKind::Lex(LexErrorKind::InvalidCharacter('@'))
+                ),
+                (
+                    "P0003",
+                    ParseErrorKind::ExpectedExpression {
+                        found: TokenKind::SemiColon
+                    }
+                ),
+                (
+                    "P0001",
+                    ParseErrorKind::UnexpectedToken {
+                        expected: vec![TokenKind::Rparen],
+                        found: TokenKind::Ident
+                    }
+                ),
+                (
+                    "P0002",
+                    ParseErrorKind::UnexpectedEof {
+                        expected: vec![TokenKind::Rparen]
+                    }
+                ),
+            ]
+        );
     }
 }
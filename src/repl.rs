@@ -1,14 +1,16 @@
 use std::io::{stdin, stdout, Result, Write};
 
-use moca::{lexer::Lexer, parser::Parser};
+use moca::{evaluator::Evaluator, lexer::Lexer, parser::Parser, value::Value};
 
 pub fn start() {
     println!(":: start repl ::");
 
+    // 変数は入力行をまたいで保持する
+    let mut evaluator = Evaluator::new();
     loop {
         match input().as_deref() {
             Ok("exit") => break,
-            Ok(line) => repl(line),
+            Ok(line) => repl(&mut evaluator, line),
             Err(err) => {
                 println!("Error: {}", err);
                 break;
@@ -18,11 +20,22 @@ pub fn start() {
     println!(":: end repl ::");
 }
 
-fn repl(line: &str) {
+fn repl(evaluator: &mut Evaluator, line: &str) {
     let lexer = Lexer::new(line);
     let mut parser = Parser::new(lexer);
 
-    parser.parse_program();
+    let result = parser.parse_program();
+    if !result.errors.is_empty() {
+        for err in &result.errors {
+            println!("{}", err);
+        }
+        return;
+    }
+    match evaluator.eval_program(&result.program) {
+        Ok(Value::Void) => {}
+        Ok(value) => println!("{}", value),
+        Err(err) => println!("{}", err),
+    }
 }
 
 fn input() -> Result<String> {
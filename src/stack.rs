@@ -0,0 +1,9 @@
+// 残りのスタックが RED_ZONE を切っていたら、GROW_SIZE のスタックをヒープに確保してそちらで f を実行する。
+// 構文解析と評価は入れ子や再帰の深さだけ Rust のスタックを使うので、
+// スレッドのスタックの大きさやビルドの種類によらず動くように、再帰の入口で呼ぶ
+const RED_ZONE: usize = 128 * 1024;
+const GROW_SIZE: usize = 1024 * 1024;
+
+pub(crate) fn ensure_sufficient_stack<R>(f: impl FnOnce() -> R) -> R {
+    stacker::maybe_grow(RED_ZONE, GROW_SIZE, f)
+}
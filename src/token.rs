@@ -1,20 +1,39 @@
+use core::fmt;
+use std::borrow::Cow;
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TokenKind {
     // operator (演算子)
-    Plus,        // +
-    Minus,       // -
-    Asterisk,    // *
-    Slash,       // /
-    Bang,        // !
-    Assign,      // =
-    Equal,       // ==
-    NotEqual,    // !=
-    LessThan,    // <
-    GreaterThan, // >
-    And,         // &&
-    Or,          // ||
-    Comma,       // ,
-    // Dot,      // .
+    Plus,           // +
+    Minus,          // -
+    Asterisk,       // *
+    Slash,          // /
+    Percent,        // %
+    Power,          // **
+    Bang,           // !
+    Assign,         // =
+    PlusAssign,     // +=
+    MinusAssign,    // -=
+    AsteriskAssign, // *=
+    SlashAssign,    // /=
+    Equal,          // ==
+    NotEqual,       // !=
+    LessThan,       // <
+    GreaterThan,    // >
+    LessEqual,      // <=
+    GreaterEqual,   // >=
+    And,            // &&
+    Or,             // ||
+    BitAnd,         // &
+    BitOr,          // |
+    BitXor,         // ^
+    ShiftLeft,      // <<
+    ShiftRight,     // >>
+    Comma,          // ,
+    Dot,            // .
+    DotDot,         // ..
+    DotDotEqual,    // ..=
+    Arrow,          // ->
 
     // separator (区切り子)
     Lparen,    // (
@@ -27,16 +46,20 @@ pub enum TokenKind {
     SemiColon, // ;
 
     // keyword
-    Void,    // void
-    Return,  // return
-    If,      // if
-    Else,    // else
-    While,   // while
-    Let,     // let
-    Fn,      // fn
-    Int,     // int
-    Double,  // double
-    Boolean, // boolean
+    Void,     // void
+    Return,   // return
+    If,       // if
+    Else,     // else
+    While,    // while
+    Break,    // break
+    Continue, // continue
+    For,      // for
+    In,       // in
+    Let,      // let
+    Fn,       // fn
+    Int,      // int
+    Double,   // double
+    Boolean,  // boolean
     // New,     // new
     // Class,   // class
 
@@ -47,58 +70,145 @@ pub enum TokenKind {
     BoolLiteral,   // 真偽リテラル
 
     // others
-    Ident, // 変数名・関数名
-    Other, // その他
+    Ident,      // 変数名・関数名
+    Label,      // ループのラベル（'outer）。value は ' を除いた名前
+    DocComment, // ドキュメントコメント（///）
+    Other,      // その他
+    Eof,        // 入力の終わり
+
+    // trivia（Lexer::lossless のときのみ）
+    Whitespace, // 空白・改行
+    Comment,    // コメント（// や /* */）
 }
 
 // #[derive(Debug, Clone)]
 pub enum Priority {
     Lowest,
-    Equals,
-    LessGreater,
-    Sum,
-    Product,
-    Prefix,
-    Call,
+    Assign,      // = += -= *= /=
+    Range,       // .. ..=
+    LogicalOr,   // ||
+    LogicalAnd,  // &&
+    Equals,      // == !=
+    LessGreater, // < > <= >=
+    BitOr,       // |
+    BitXor,      // ^
+    BitAnd,      // &
+    Shift,       // << >>
+    Sum,         // + -
+    Product,     // * / %
+    Prefix,      // -x !x
+    Power,       // **
+    Call,        // f(x) a.b a[i]
 }
 
 pub fn get_priority(token_kind: TokenKind) -> Priority {
     match token_kind {
+        TokenKind::Assign
+        | TokenKind::PlusAssign
+        | TokenKind::MinusAssign
+        | TokenKind::AsteriskAssign
+        | TokenKind::SlashAssign => Priority::Assign,
+        TokenKind::DotDot | TokenKind::DotDotEqual => Priority::Range,
+        TokenKind::Or => Priority::LogicalOr,
+        TokenKind::And => Priority::LogicalAnd,
         TokenKind::Equal | TokenKind::NotEqual => Priority::Equals,
-        TokenKind::LessThan | TokenKind::GreaterThan => Priority::LessGreater,
+        TokenKind::LessThan
+        | TokenKind::GreaterThan
+        | TokenKind::LessEqual
+        | TokenKind::GreaterEqual => Priority::LessGreater,
+        TokenKind::BitOr => Priority::BitOr,
+        TokenKind::BitXor => Priority::BitXor,
+        TokenKind::BitAnd => Priority::BitAnd,
+        TokenKind::ShiftLeft | TokenKind::ShiftRight => Priority::Shift,
         TokenKind::Plus | TokenKind::Minus => Priority::Sum,
-        TokenKind::Asterisk | TokenKind::Slash => Priority::Product,
-        TokenKind::Lparen => Priority::Call,
+        TokenKind::Asterisk | TokenKind::Slash | TokenKind::Percent => Priority::Product,
+        TokenKind::Power => Priority::Power,
+        TokenKind::Lparen | TokenKind::Dot | TokenKind::Lsquare => Priority::Call,
         _ => Priority::Lowest,
     }
 }
 
-// #[derive(Debug, Clone)]
-// pub enum Value {
-//     Int(i32),    // 整数リテラル
-//     Double(f64), // 小数リテラル
-//     Str(String), // 文字列リテラル
-//     Bool(bool),  // 真偽リテラル
-//     Symbol(String),
-//     None,
-// }
+// 右結合の演算子かどうか（a ** b ** c は a ** (b ** c)）
+pub fn is_right_associative(token_kind: TokenKind) -> bool {
+    matches!(
+        token_kind,
+        TokenKind::Power
+            | TokenKind::Assign
+            | TokenKind::PlusAssign
+            | TokenKind::MinusAssign
+            | TokenKind::AsteriskAssign
+            | TokenKind::SlashAssign
+    )
+}
+
+// ソース上の位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,  // 開始位置（バイトオフセット）
+    pub end: usize,    // 終了位置（バイトオフセット、含まない）
+    pub line: usize,   // 開始行（1始まり）
+    pub column: usize, // 開始列（1始まり、文字単位）
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize, line: usize, column: usize) -> Self {
+        Span {
+            start,
+            end,
+            line,
+            column,
+        }
+    }
+}
 
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+// value は Lexer のソースを借用する。ソースより長く保持したい場合は into_owned を使う
 #[derive(Debug, Clone)]
-pub struct Token {
+pub struct Token<'src> {
     pub token_kind: TokenKind,
-    pub value: String,
+    pub value: Cow<'src, str>,
+    pub span: Span,
 }
 
-impl Token {
-    pub fn new(token_kind: TokenKind, value: String) -> Self {
-        Token { token_kind, value }
+impl<'src> Token<'src> {
+    pub fn new(token_kind: TokenKind, value: impl Into<Cow<'src, str>>, span: Span) -> Self {
+        Token {
+            token_kind,
+            value: value.into(),
+            span,
+        }
     }
 
     pub fn is_same_kind(&self, kind: TokenKind) -> bool {
         self.token_kind == kind
     }
 
-    pub fn to_string(&self) -> String {
-        format!("Token: {:?}, {:?}", self.token_kind, self.value)
+    // ソース上の元の文字列（文字列リテラルなら " やエスケープも含む）
+    pub fn text<'a>(&self, src: &'a str) -> &'a str {
+        &src[self.span.start..self.span.end]
+    }
+
+    // ソースを借用しないトークンにする
+    pub fn into_owned(self) -> Token<'static> {
+        Token {
+            token_kind: self.token_kind,
+            value: Cow::Owned(self.value.into_owned()),
+            span: self.span,
+        }
+    }
+}
+
+impl fmt::Display for Token<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Token: {:?}, {:?} at {}",
+            self.token_kind, self.value, self.span
+        )
     }
 }
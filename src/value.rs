@@ -0,0 +1,357 @@
+use core::fmt;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::rc::Rc;
+
+use crate::ast::expression::{escape_string, write_signature, Parameter};
+use crate::ast::statement::Statement;
+use crate::ast::types::Type;
+use crate::environment::Env;
+use crate::stack::ensure_sufficient_stack;
+
+// 評価結果の値
+#[derive(Debug, Clone)]
+pub enum Value {
+    Int(i32),
+    Double(f64),
+    Bool(bool),
+    Str(String),
+    Array(Rc<RefCell<Array>>), // 参照型（代入しても同じ配列を指す）
+    Hash(Rc<RefCell<Hash>>),   // 配列と同じく参照型
+    Range {
+        start: i32,
+        end: i32,
+        inclusive: bool,
+    },
+    Function(Rc<Function>),
+    Void,
+}
+
+impl Value {
+    // 型注釈の検査やエラーメッセージに使う値の型
+    pub fn value_type(&self) -> Type {
+        match self {
+            Value::Int(_) => Type::Int,
+            Value::Double(_) => Type::Double,
+            Value::Bool(_) => Type::Boolean,
+            Value::Str(_) => Type::String,
+            Value::Array(_) => Type::Array,
+            Value::Hash(_) => Type::Hash,
+            Value::Range { .. } => Type::Range,
+            Value::Function(_) => Type::Function,
+            Value::Void => Type::Void,
+        }
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        values_equal(self, other, &mut HashSet::new())
+    }
+}
+
+// 比較中の配列（またはハッシュ）の組
+type Comparing = HashSet<(*const (), *const ())>;
+
+// 配列やハッシュは代入で自分自身を含みうるので、比較中の組を comparing に入れておく。
+// 同じ組の比較に戻ってきたら、ほかの要素で違いが見つからない限り等しいとみなす
+fn values_equal(a: &Value, b: &Value, comparing: &mut Comparing) -> bool {
+    match (a, b) {
+        (Value::Int(a), Value::Int(b)) => a == b,
+        (Value::Double(a), Value::Double(b)) => a == b,
+        (Value::Int(a), Value::Double(b)) | (Value::Double(b), Value::Int(a)) => {
+            f64::from(*a) == *b
+        }
+        (Value::Bool(a), Value::Bool(b)) => a == b,
+        (Value::Str(a), Value::Str(b)) => a == b,
+        (Value::Array(a), Value::Array(b)) => {
+            let pair = (Rc::as_ptr(a) as *const (), Rc::as_ptr(b) as *const ());
+            if Rc::ptr_eq(a, b) || !comparing.insert(pair) {
+                return true;
+            }
+            let (a, b) = (a.borrow(), b.borrow());
+            let equal = a.len() == b.len()
+                && a.iter()
+                    .zip(b.iter())
+                    .all(|(a, b)| ensure_sufficient_stack(|| values_equal(a, b, comparing)));
+            comparing.remove(&pair);
+            equal
+        }
+        (Value::Hash(a), Value::Hash(b)) => {
+            let pair = (Rc::as_ptr(a) as *const (), Rc::as_ptr(b) as *const ());
+            if Rc::ptr_eq(a, b) || !comparing.insert(pair) {
+                return true;
+            }
+            let equal = hashes_equal(&a.borrow(), &b.borrow(), comparing);
+            comparing.remove(&pair);
+            equal
+        }
+        (
+            Value::Range {
+                start: s1,
+                end: e1,
+                inclusive: i1,
+            },
+            Value::Range {
+                start: s2,
+                end: e2,
+                inclusive: i2,
+            },
+        ) => (s1, e1, i1) == (s2, e2, i2),
+        (Value::Function(a), Value::Function(b)) => Rc::ptr_eq(a, b),
+        (Value::Void, Value::Void) => true,
+        _ => false,
+    }
+}
+
+// 順番によらず、同じキーに同じ値が入っていれば等しい
+fn hashes_equal(a: &Hash, b: &Hash, comparing: &mut Comparing) -> bool {
+    a.len() == b.len()
+        && a.iter().all(|(key, value)| {
+            b.get(key).is_some_and(|other| {
+                ensure_sufficient_stack(|| values_equal(value, other, comparing))
+            })
+        })
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_value(f, self, false, &mut HashSet::new())
+    }
+}
+
+// quote なら文字列を引用符付きで書く（配列やハッシュの要素の文字列を区別できるように）
+// visiting は表示中の配列とハッシュ。自分自身を含む部分は [...] や {...} と書く
+fn write_value(
+    f: &mut fmt::Formatter<'_>,
+    value: &Value,
+    quote: bool,
+    visiting: &mut HashSet<*const ()>,
+) -> fmt::Result {
+    match value {
+        Value::Int(i) => write!(f, "{}", i),
+        // 1.0, NaN, inf, -inf
+        Value::Double(d) => write!(f, "{:?}", d),
+        Value::Bool(b) => write!(f, "{}", b),
+        Value::Str(s) if quote => write!(f, "\"{}\"", escape_string(s)),
+        Value::Str(s) => write!(f, "{}", s),
+        Value::Array(elements) => {
+            let ptr = Rc::as_ptr(elements) as *const ();
+            if !visiting.insert(ptr) {
+                return write!(f, "[...]");
+            }
+            write!(f, "[")?;
+            for (i, element) in elements.borrow().iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                ensure_sufficient_stack(|| write_value(f, element, true, visiting))?;
+            }
+            visiting.remove(&ptr);
+            write!(f, "]")
+        }
+        Value::Hash(hash) => {
+            let ptr = Rc::as_ptr(hash) as *const ();
+            if !visiting.insert(ptr) {
+                return write!(f, "{{...}}");
+            }
+            write!(f, "{{")?;
+            for (i, (key, value)) in hash.borrow().iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}: ", key)?;
+                ensure_sufficient_stack(|| write_value(f, value, true, visiting))?;
+            }
+            visiting.remove(&ptr);
+            write!(f, "}}")
+        }
+        Value::Range {
+            start,
+            end,
+            inclusive,
+        } => {
+            let operator = if *inclusive { "..=" } else { ".." };
+            write!(f, "{}{}{}", start, operator, end)
+        }
+        Value::Function(func) => write!(f, "{}", func),
+        Value::Void => write!(f, "void"),
+    }
+}
+
+// ハッシュのキーにできる値（double は NaN があるので使えない）
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum HashKey {
+    Int(i32),
+    Bool(bool),
+    Str(String),
+}
+
+impl TryFrom<Value> for HashKey {
+    type Error = Value;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        match value {
+            Value::Int(i) => Ok(HashKey::Int(i)),
+            Value::Bool(b) => Ok(HashKey::Bool(b)),
+            Value::Str(s) => Ok(HashKey::Str(s)),
+            other => Err(other),
+        }
+    }
+}
+
+impl From<HashKey> for Value {
+    fn from(key: HashKey) -> Self {
+        match key {
+            HashKey::Int(i) => Value::Int(i),
+            HashKey::Bool(b) => Value::Bool(b),
+            HashKey::Str(s) => Value::Str(s),
+        }
+    }
+}
+
+// 文字列のキーは引用符付きで表示する
+impl fmt::Display for HashKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HashKey::Int(i) => write!(f, "{}", i),
+            HashKey::Bool(b) => write!(f, "{}", b),
+            HashKey::Str(s) => write!(f, "\"{}\"", escape_string(s)),
+        }
+    }
+}
+
+// 挿入順を保つハッシュ。表示やイテレーションは最初に入れた順になる
+#[derive(Debug, Clone, Default)]
+pub struct Hash {
+    entries: Vec<(HashKey, Value)>,
+    index: HashMap<HashKey, usize>, // キー → entries の位置
+}
+
+impl Hash {
+    pub fn new() -> Self {
+        Hash::default()
+    }
+
+    pub fn get(&self, key: &HashKey) -> Option<&Value> {
+        self.index.get(key).map(|&i| &self.entries[i].1)
+    }
+
+    // 既にあるキーは値だけを書き換える（順番は変わらない）
+    pub fn insert(&mut self, key: HashKey, value: Value) {
+        match self.index.get(&key) {
+            Some(&i) => self.entries[i].1 = value,
+            None => {
+                self.index.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value));
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &(HashKey, Value)> {
+        self.entries.iter()
+    }
+}
+
+impl PartialEq for Hash {
+    fn eq(&self, other: &Self) -> bool {
+        hashes_equal(self, other, &mut HashSet::new())
+    }
+}
+
+impl Drop for Hash {
+    fn drop(&mut self) {
+        let entries = mem::take(&mut self.entries);
+        drop_values(entries.into_iter().map(|(_, value)| value).collect());
+    }
+}
+
+// 配列の中身。Vec<Value> として使える
+#[derive(Debug, Clone, Default)]
+pub struct Array(Vec<Value>);
+
+impl From<Vec<Value>> for Array {
+    fn from(elements: Vec<Value>) -> Self {
+        Array(elements)
+    }
+}
+
+impl Deref for Array {
+    type Target = Vec<Value>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for Array {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl Drop for Array {
+    fn drop(&mut self) {
+        drop_values(mem::take(&mut self.0));
+    }
+}
+
+// 深く入れ子になった配列やハッシュを再帰せずに解放する。
+// 最後の参照だった中身を取り出して values に積み、空になった入れ物だけを drop する
+fn drop_values(mut values: Vec<Value>) {
+    while let Some(value) = values.pop() {
+        match value {
+            Value::Array(elements) => {
+                if let Ok(elements) = Rc::try_unwrap(elements) {
+                    values.append(&mut elements.into_inner());
+                }
+            }
+            Value::Hash(hash) => {
+                if let Ok(hash) = Rc::try_unwrap(hash) {
+                    let mut hash = hash.into_inner();
+                    let entries = mem::take(&mut hash.entries);
+                    values.extend(entries.into_iter().map(|(_, value)| value));
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+// 関数値。定義時の環境を捕捉する
+pub struct Function {
+    pub name: Option<String>, // 宣言された関数の名前（関数リテラルなら None）
+    pub parameters: Vec<Parameter>,
+    pub return_type: Option<Type>,
+    pub body: Statement, // BlockStatement
+    pub env: Env,
+}
+
+// env は循環参照になりうるので出力しない
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Function")
+            .field("name", &self.name)
+            .field("parameters", &self.parameters)
+            .field("return_type", &self.return_type)
+            .field("body", &self.body)
+            .finish_non_exhaustive()
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_signature(f, self.name.as_deref(), &self.parameters, &self.return_type)?;
+        write!(f, " {}", self.body)
+    }
+}